@@ -1,14 +1,18 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::Local;
 use clap::{crate_version, App, Arg};
+use std::path::Path;
+
+use crate::info::{Compression, SortOrder, Strand};
 
 /// A struct that contains and initiates all input arguments
+#[derive(Clone)]
 pub struct Args {
-    pub fastq: String,                           // fastq file path
-    pub format: String,                          // format scheme file path
-    pub sample_barcodes_option: Option<String>,  // sample barcode file path.  Optional
-    pub counted_barcodes_option: Option<String>, // building block barcode file path. Optional
-    pub output_dir: String,                      // output directory.  Deafaults to './'
+    pub fastq: String,                          // fastq file path
+    pub formats: Vec<String>,                   // format scheme file paths.  One for each library
+    pub sample_barcodes_option: Option<String>, // sample barcode file path.  Optional
+    pub counted_barcodes_files: Vec<String>,    // barcode file paths, one per format.  Optional
+    pub output_dir: String,                     // output directory.  Deafaults to './'
     pub threads: u16, // Number of threads to use.  Defaults to number of threads on the machine
     pub prefix: String, // Prefix string for the output files
     pub merge_output: bool, // Whether or not to create an additional output file that merges all samples
@@ -17,6 +21,18 @@ pub struct Args {
     pub constant_errors_option: Option<u16>, // Optional input of how many errors are allowed in each constant region barcode.  Defaults to 20% of the length
     pub min_average_quality_score: f32,
     pub enrich: bool,
+    pub strand: Strand, // Which strand of the read to search for the format.  Defaults to forward
+    pub fail_on_collision: bool, // Whether to stop when barcodes are too similar for the errors allowed, instead of warning
+    pub multiqc: bool,           // Whether to write MultiQC custom content files
+    pub include_zero: bool, // Whether to write every expected library member, including those with no counts
+    pub expected_combinations_files: Vec<String>, // expected counted barcode combination file paths, one per format.  Optional
+    pub sort: SortOrder,                          // Order of the rows within the counts files
+    pub compress: Option<Compression>, // Compression of the counts files.  None writes plain CSV
+    pub top_barcodes: usize, // Number of sequences listed within the undetermined sample and unknown counted barcode files
+    pub arrow: bool, // Whether to also write the sample and merged counts as Arrow IPC files
+    pub long: bool,  // Whether to also write the counts of all samples within one long format file
+    pub mtx: bool, // Whether to also write the counts of all samples as a Matrix Market sparse matrix
+    pub sequence_and_id: bool, // Whether to write the DNA sequence next to the ID of each converted counted barcode
 }
 
 impl Args {
@@ -41,8 +57,9 @@ impl Args {
                 .short("q")
                 .long("sequence-format")
                 .takes_value(true)
+                .multiple(true)
                 .required(true)
-                .help("Sequence format file"),
+                .help("Sequence format file.  Multiple files can be given for runs that pool libraries with different formats"),
         )
         .arg(
             Arg::with_name("sample_file")
@@ -56,7 +73,8 @@ impl Args {
                 .short("c")
                 .long("counted-barcodes")
                 .takes_value(true)
-                .help("Counted barcodes file"),
+                .multiple(true)
+                .help("Counted barcodes file.  One for each sequence format, in the same order"),
         )
         .arg(
             Arg::with_name("threads")
@@ -121,6 +139,85 @@ impl Args {
                 .default_value("0")
                 .help("Minimum average read quality score per barcode"),
         )
+        .arg(
+            Arg::with_name("strand")
+                .long("strand")
+                .takes_value(true)
+                .possible_values(&["forward", "reverse", "both"])
+                .default_value("forward")
+                .help("Read strand to search for the sequence format.  'both' searches the reverse complement when the forward read does not match"),
+        )
+        .arg(
+            Arg::with_name("fail_on_collision")
+                .long("fail-on-collision")
+                .takes_value(false)
+                .help("Stop before counting when barcodes within a set are too similar for the maximum errors allowed, instead of warning"),
+        )
+        .arg(
+            Arg::with_name("multiqc")
+                .long("multiqc")
+                .takes_value(false)
+                .help("Also write the read errors and counted reads per sample as MultiQC custom content files (_mqc.tsv)"),
+        )
+        .arg(
+            Arg::with_name("include_zero")
+                .long("include-zero")
+                .takes_value(false)
+                .help("Write every library member within the counts files, including those with no counts.  Needs a counted barcode conversion and, with more than one counted barcode, --expected-combinations"),
+        )
+        .arg(
+            Arg::with_name("expected_combinations")
+                .long("expected-combinations")
+                .takes_value(true)
+                .multiple(true)
+                .help("File of the counted barcode combinations expected within the library.  One file is needed for each sequence format"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(&["count", "id", "sequence"])
+                .default_value("count")
+                .help("Order of the rows within the counts files.  'count' is from most to least counted, 'id' is by barcode ID, and 'sequence' is by DNA barcode"),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("compress")
+                .takes_value(true)
+                .possible_values(&["gzip", "zstd"])
+                .help("Compress the sample, merged, and enrichment counts files.  Adds .gz or .zst to the file names"),
+        )
+        .arg(
+            Arg::with_name("top_barcodes")
+                .long("top-barcodes")
+                .takes_value(true)
+                .default_value("20")
+                .help("Number of the most frequent sequences listed within the undetermined sample barcode file and for each counted barcode within the unknown barcode file"),
+        )
+        .arg(
+            Arg::with_name("arrow")
+                .long("arrow")
+                .takes_value(false)
+                .help("Also write the sample and merged counts as Arrow IPC files (.arrow) with typed columns, for loading with Polars, pandas, or R"),
+        )
+        .arg(
+            Arg::with_name("long")
+                .long("long")
+                .takes_value(false)
+                .help("Also write the counts of all samples within one long format file, with a row for each sample and counted barcode combination"),
+        )
+        .arg(
+            Arg::with_name("mtx")
+                .long("mtx")
+                .takes_value(false)
+                .help("Also write the counts of all samples as a Matrix Market sparse matrix, with barcode combination and sample label files, in the 10x Genomics layout read by scanpy and Seurat"),
+        )
+        .arg(
+            Arg::with_name("sequence_and_id")
+                .long("sequence-and-id")
+                .takes_value(false)
+                .help("Write the DNA sequence of each counted barcode next to its ID within the counts files.  Only applies to counted barcodes with a conversion file"),
+        )
         .get_matches();
 
         let sample_barcodes_option;
@@ -130,11 +227,42 @@ impl Args {
             sample_barcodes_option = None
         }
 
-        let counted_barcodes_option;
-        if let Some(barcodes) = args.value_of("barcode_file") {
-            counted_barcodes_option = Some(barcodes.to_string())
+        let formats = args
+            .values_of("format_file")
+            .unwrap()
+            .map(|format| format.to_string())
+            .collect::<Vec<String>>();
+
+        let counted_barcodes_files;
+        if let Some(barcodes) = args.values_of("barcode_file") {
+            counted_barcodes_files = barcodes
+                .map(|barcode_file| barcode_file.to_string())
+                .collect::<Vec<String>>();
+            if counted_barcodes_files.len() != formats.len() {
+                bail!(
+                    "{} sequence format files were given with {} counted barcode files.  One counted barcode file is needed for each format",
+                    formats.len(),
+                    counted_barcodes_files.len()
+                )
+            }
+        } else {
+            counted_barcodes_files = Vec::new()
+        }
+
+        let expected_combinations_files;
+        if let Some(combinations) = args.values_of("expected_combinations") {
+            expected_combinations_files = combinations
+                .map(|combination_file| combination_file.to_string())
+                .collect::<Vec<String>>();
+            if expected_combinations_files.len() != formats.len() {
+                bail!(
+                    "{} sequence format files were given with {} expected combination files.  One expected combination file is needed for each format",
+                    formats.len(),
+                    expected_combinations_files.len()
+                )
+            }
         } else {
-            counted_barcodes_option = None
+            expected_combinations_files = Vec::new()
         }
 
         let barcodes_errors_option;
@@ -172,8 +300,14 @@ impl Args {
 
         let merge_output = args.is_present("merge-output");
         let enrich = args.is_present("enrich");
+        let fail_on_collision = args.is_present("fail_on_collision");
+        let multiqc = args.is_present("multiqc");
+        let include_zero = args.is_present("include_zero");
+        let arrow = args.is_present("arrow");
+        let long = args.is_present("long");
+        let mtx = args.is_present("mtx");
+        let sequence_and_id = args.is_present("sequence_and_id");
         let fastq = args.value_of("fastq").unwrap().to_string();
-        let format = args.value_of("format_file").unwrap().to_string();
         let output_dir = args.value_of("dir").unwrap().to_string();
         let threads = args
             .value_of("threads")
@@ -186,12 +320,32 @@ impl Args {
             .unwrap()
             .parse::<f32>()
             .context("Unable to convert min score to a float")?;
+        let top_barcodes = args
+            .value_of("top_barcodes")
+            .unwrap()
+            .parse::<usize>()
+            .context("Unable to convert top barcodes to an integer")?;
+        let sort = match args.value_of("sort").unwrap() {
+            "id" => SortOrder::Id,
+            "sequence" => SortOrder::Sequence,
+            _ => SortOrder::Count,
+        };
+        let compress = match args.value_of("compress") {
+            Some("gzip") => Some(Compression::Gzip),
+            Some("zstd") => Some(Compression::Zstd),
+            _ => None,
+        };
+        let strand = match args.value_of("strand").unwrap() {
+            "reverse" => Strand::Reverse,
+            "both" => Strand::Both,
+            _ => Strand::Forward,
+        };
 
         Ok(Args {
             fastq,
-            format,
+            formats,
             sample_barcodes_option,
-            counted_barcodes_option,
+            counted_barcodes_files,
             output_dir,
             threads,
             prefix,
@@ -201,6 +355,47 @@ impl Args {
             constant_errors_option,
             min_average_quality_score,
             enrich,
+            strand,
+            fail_on_collision,
+            multiqc,
+            include_zero,
+            expected_combinations_files,
+            sort,
+            compress,
+            top_barcodes,
+            arrow,
+            long,
+            mtx,
+            sequence_and_id,
         })
     }
+
+    /// Returns the output file prefix for the sequence format at format_index.  With more than one format, the format
+    /// file name is added to the prefix so that each format is written separately.  Formats whose file names are the same,
+    /// such as a/lib.txt and b/lib.txt, are named by their position instead so that one cannot overwrite the other
+    pub fn format_prefix(&self, format_index: usize) -> String {
+        if self.formats.len() > 1 {
+            let format_stem = |format_path: &String| {
+                Path::new(format_path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+            };
+            let format_name = match format_stem(&self.formats[format_index]) {
+                Some(stem)
+                    if self
+                        .formats
+                        .iter()
+                        .filter(|format_path| format_stem(format_path).as_ref() == Some(&stem))
+                        .count()
+                        == 1 =>
+                {
+                    stem
+                }
+                _ => format!("format{}", format_index + 1),
+            };
+            format!("{}_{}", self.prefix, format_name)
+        } else {
+            self.prefix.clone()
+        }
+    }
 }
@@ -1,13 +1,16 @@
 use ahash::{AHashSet, HashMap, HashMapExt};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{bail, Context, Result};
 use itertools::Itertools;
 use num_format::{Locale, ToFormattedString};
+use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
     fmt, fs,
+    path::Path,
     sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc,
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
 };
 
@@ -17,9 +20,22 @@ pub struct SequenceErrors {
     constant_region: Arc<AtomicU32>, // errors within the constant region
     sample_barcode: Arc<AtomicU32>,  // errors within the sample barcode
     barcode: Arc<AtomicU32>,         // erors within the counted barcode
+    constant_region_ambiguous: Arc<AtomicU32>, // constant regions equally close to more than one position or format
+    sample_barcode_ambiguous: Arc<AtomicU32>, // sample barcodes equally close to more than one known sample barcode
+    barcode_ambiguous: Arc<AtomicU32>, // counted barcodes equally close to more than one known counted barcode
+    impossible_combination: Arc<AtomicU32>, // counted barcode combinations not within the expected library
     matched: Arc<AtomicU32>,         // total matched
     duplicates: Arc<AtomicU32>,      // total random barcode duplicates
     low_quality: Arc<AtomicU32>,     // total random barcode duplicates
+    forward_strand: Arc<AtomicU32>,  // total matched on the forward strand
+    reverse_strand: Arc<AtomicU32>,  // total matched on the reverse strand
+    constant_region_matches: RegionErrors, // constant region matches by the number of mismatches
+    sample_barcode_matches: Option<RegionErrors>, // sample barcode matches by the number of mismatches, if there is a sample barcode
+    barcode_matches: Vec<RegionErrors>, // matches by the number of mismatches for each counted barcode
+    constant_mismatch_profile: MismatchProfile, // positions of the mismatches within corrected constant regions
+    sample_reads: SampleReads,                  // outcome of the reads for each sample barcode
+    undetermined_sample_barcodes: BarcodeTally, // raw sample barcodes of the reads that failed sample assignment
+    unknown_barcodes: Vec<BarcodeTally>, // raw counted barcodes that could not be fixed, for each counted barcode
 }
 
 impl Default for SequenceErrors {
@@ -42,12 +58,63 @@ impl SequenceErrors {
             constant_region: Arc::new(AtomicU32::new(0)),
             sample_barcode: Arc::new(AtomicU32::new(0)),
             barcode: Arc::new(AtomicU32::new(0)),
+            constant_region_ambiguous: Arc::new(AtomicU32::new(0)),
+            sample_barcode_ambiguous: Arc::new(AtomicU32::new(0)),
+            barcode_ambiguous: Arc::new(AtomicU32::new(0)),
+            impossible_combination: Arc::new(AtomicU32::new(0)),
             matched: Arc::new(AtomicU32::new(0)),
             duplicates: Arc::new(AtomicU32::new(0)),
             low_quality: Arc::new(AtomicU32::new(0)),
+            forward_strand: Arc::new(AtomicU32::new(0)),
+            reverse_strand: Arc::new(AtomicU32::new(0)),
+            constant_region_matches: RegionErrors::new("Constant region", 0),
+            sample_barcode_matches: None,
+            barcode_matches: Vec::new(),
+            constant_mismatch_profile: MismatchProfile::new(0),
+            sample_reads: SampleReads::new(),
+            undetermined_sample_barcodes: BarcodeTally::new(),
+            unknown_barcodes: Vec::new(),
         }
     }
 
+    /// Create a new sequence error struct which also counts the matches and failures of each region within the sequence format.
+    /// Matches are counted for each number of mismatches up to the maximum errors allowed for the region
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatRegion, MaxSeqErrors, SequenceErrors, SequenceFormat};
+    ///
+    /// let regions = FormatRegion::from_format_string("AGCTAGCTAG\n{8}\nTTGA\n{8}").unwrap();
+    /// let sequence_format = SequenceFormat::from_regions(&regions).unwrap();
+    /// let max_errors = MaxSeqErrors::new(None, None, None, vec![8, 8], None, 14, 0.0);
+    /// let mut sequence_errors = SequenceErrors::new_with_regions(&sequence_format, &max_errors);
+    /// sequence_errors.barcode_match(1, 1);
+    /// ```
+    pub fn new_with_regions(sequence_format: &SequenceFormat, max_errors: &MaxSeqErrors) -> Self {
+        let mut sequence_errors = SequenceErrors::new();
+        sequence_errors.constant_region_matches =
+            RegionErrors::new("Constant region", max_errors.max_constant_errors());
+        if sequence_format.sample_barcode {
+            sequence_errors.sample_barcode_matches = Some(RegionErrors::new(
+                "Sample barcode",
+                max_errors.max_sample_errors(),
+            ));
+        }
+        sequence_errors.barcode_matches = sequence_format
+            .barcode_names
+            .iter()
+            .zip(max_errors.max_barcode_errors())
+            .map(|(barcode_name, barcode_errors)| RegionErrors::new(barcode_name, *barcode_errors))
+            .collect();
+        sequence_errors.constant_mismatch_profile = MismatchProfile::new(sequence_format.length);
+        sequence_errors.unknown_barcodes = sequence_format
+            .barcode_names
+            .iter()
+            .map(|_| BarcodeTally::new())
+            .collect();
+        sequence_errors
+    }
+
     /// Add one to constant region error
     ///
     /// # Example
@@ -59,6 +126,7 @@ impl SequenceErrors {
     /// ```
     pub fn constant_region_error(&mut self) {
         self.constant_region.fetch_add(1, Ordering::Relaxed);
+        self.constant_region_matches.failed();
     }
 
     /// Add one to sample barcode error
@@ -72,19 +140,257 @@ impl SequenceErrors {
     /// ```
     pub fn sample_barcode_error(&mut self) {
         self.sample_barcode.fetch_add(1, Ordering::Relaxed);
+        if let Some(sample_barcode_matches) = self.sample_barcode_matches.as_mut() {
+            sample_barcode_matches.failed();
+        }
     }
 
-    /// Add one to barcode error
+    /// Add one to barcode error, along with the failures of the counted barcode at barcode_index
     ///
     /// # Example
     /// ```
     /// use barcode_count::info::SequenceErrors;
     ///
     /// let mut sequence_errors = SequenceErrors::new();
-    /// sequence_errors.barcode_error();
+    /// sequence_errors.barcode_error(0);
     /// ```
-    pub fn barcode_error(&mut self) {
+    pub fn barcode_error(&mut self, barcode_index: usize) {
         self.barcode.fetch_add(1, Ordering::Relaxed);
+        if let Some(barcode_matches) = self.barcode_matches.get_mut(barcode_index) {
+            barcode_matches.failed();
+        }
+    }
+
+    /// Add one to constant region ambiguous, where the best constant region match was tied
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.constant_region_ambiguous();
+    /// ```
+    pub fn constant_region_ambiguous(&mut self) {
+        self.constant_region_ambiguous
+            .fetch_add(1, Ordering::Relaxed);
+        self.constant_region_matches.failed();
+    }
+
+    /// Add one to sample barcode ambiguous, where two or more sample barcodes were tied for the best match
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.sample_barcode_ambiguous();
+    /// ```
+    pub fn sample_barcode_ambiguous(&mut self) {
+        self.sample_barcode_ambiguous
+            .fetch_add(1, Ordering::Relaxed);
+        if let Some(sample_barcode_matches) = self.sample_barcode_matches.as_mut() {
+            sample_barcode_matches.failed();
+        }
+    }
+
+    /// Add one to barcode ambiguous, where two or more counted barcodes were tied for the best match, along with the failures
+    /// of the counted barcode at barcode_index
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.barcode_ambiguous(0);
+    /// ```
+    pub fn barcode_ambiguous(&mut self, barcode_index: usize) {
+        self.barcode_ambiguous.fetch_add(1, Ordering::Relaxed);
+        if let Some(barcode_matches) = self.barcode_matches.get_mut(barcode_index) {
+            barcode_matches.failed();
+        }
+    }
+
+    /// Add one to the constant region matches with the number of mismatches
+    pub fn constant_region_match(&mut self, mismatches: u16) {
+        self.constant_region_matches.matched(mismatches);
+    }
+
+    /// Add a read with a corrected constant region to the mismatch profile, along with the format string positions of its mismatches
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.constant_region_corrected(&[3]);
+    /// assert_eq!(sequence_errors.constant_mismatch_profile().corrected(), 1);
+    /// ```
+    pub fn constant_region_corrected(&mut self, mismatch_positions: &[usize]) {
+        self.constant_mismatch_profile
+            .add_corrected(mismatch_positions);
+    }
+
+    /// Returns the current count of each error category
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.correct_match();
+    /// sequence_errors.duplicated();
+    /// let error_counts = sequence_errors.counts();
+    /// assert_eq!(error_counts.matched, 1);
+    /// assert_eq!(error_counts.duplicates, 1);
+    /// ```
+    pub fn counts(&self) -> ErrorCounts {
+        ErrorCounts {
+            matched: self.matched.load(Ordering::Relaxed),
+            constant_region_mismatches: self.constant_region.load(Ordering::Relaxed),
+            constant_region_ambiguous: self.constant_region_ambiguous.load(Ordering::Relaxed),
+            sample_barcode_mismatches: self.sample_barcode.load(Ordering::Relaxed),
+            sample_barcode_ambiguous: self.sample_barcode_ambiguous.load(Ordering::Relaxed),
+            counted_barcode_mismatches: self.barcode.load(Ordering::Relaxed),
+            counted_barcode_ambiguous: self.barcode_ambiguous.load(Ordering::Relaxed),
+            impossible_combinations: self.impossible_combination.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+            low_quality: self.low_quality.load(Ordering::Relaxed),
+            forward_strand: self.forward_strand.load(Ordering::Relaxed),
+            reverse_strand: self.reverse_strand.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the match counts of each region in the order constant region, sample barcode, then counted barcodes.  Empty
+    /// unless created with the regions of a sequence format
+    pub fn region_matches(&self) -> Vec<&RegionErrors> {
+        let mut region_matches = Vec::new();
+        if !self.barcode_matches.is_empty() {
+            region_matches.push(&self.constant_region_matches);
+            region_matches.extend(self.sample_barcode_matches.iter());
+            region_matches.extend(self.barcode_matches.iter());
+        }
+        region_matches
+    }
+
+    /// Returns the positions of the mismatches within corrected constant regions
+    pub fn constant_mismatch_profile(&self) -> &MismatchProfile {
+        &self.constant_mismatch_profile
+    }
+
+    /// Add one to the reads where the combination of counted barcodes is not within the expected library
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.impossible_combination();
+    /// assert_eq!(sequence_errors.counts().impossible_combinations, 1);
+    /// ```
+    pub fn impossible_combination(&mut self) {
+        self.impossible_combination.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Add one to the read outcome of the sample barcode.  Use None for reads which failed before the sample barcode was
+    /// known, which are counted as undetermined
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{ReadOutcome, SequenceErrors};
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.sample_read(Some("AGCTAGCT"), ReadOutcome::Matched);
+    /// sequence_errors.sample_read(None, ReadOutcome::ConstantRegionError);
+    /// assert_eq!(sequence_errors.sample_reads().undetermined().constant_region_errors, 1);
+    /// ```
+    pub fn sample_read(&mut self, sample_barcode_option: Option<&str>, outcome: ReadOutcome) {
+        self.sample_reads.add(sample_barcode_option, outcome);
+    }
+
+    /// Sets the sample barcodes that are known before any reads are parsed, so that their read outcomes are counted without locking
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{ReadOutcome, SequenceErrors};
+    ///
+    /// let samples_barcode_hash = [("AGCTAGCT".to_string(), "Sample_1".to_string())].into_iter().collect();
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.set_sample_barcodes(&samples_barcode_hash, true);
+    /// sequence_errors.sample_read(Some("AGCTAGCT"), ReadOutcome::Matched);
+    /// assert_eq!(sequence_errors.sample_reads().sample_counts()[0].1.matched, 1);
+    /// ```
+    pub fn set_sample_barcodes(
+        &mut self,
+        samples_barcode_hash: &HashMap<String, String>,
+        sample_barcode: bool,
+    ) {
+        self.sample_reads
+            .set_sample_barcodes(samples_barcode_hash, sample_barcode);
+    }
+
+    /// Returns the read outcomes counted for each sample barcode
+    pub fn sample_reads(&self) -> &SampleReads {
+        &self.sample_reads
+    }
+
+    /// Add one to the tally of the raw sample barcode sequence from a read that failed sample assignment
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::SequenceErrors;
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.undetermined_sample_barcode("AAAAAAAA");
+    /// sequence_errors.undetermined_sample_barcode("AAAAAAAA");
+    /// assert_eq!(sequence_errors.undetermined_sample_barcodes().top(1), vec![("AAAAAAAA".to_string(), 2)]);
+    /// ```
+    pub fn undetermined_sample_barcode(&mut self, sample_barcode: &str) {
+        self.undetermined_sample_barcodes.add(sample_barcode);
+    }
+
+    /// Returns the tally of raw sample barcodes from reads that failed sample assignment
+    pub fn undetermined_sample_barcodes(&self) -> &BarcodeTally {
+        &self.undetermined_sample_barcodes
+    }
+
+    /// Add one to the tally of the raw counted barcode sequence at barcode_index that could not be fixed.  Only counted when
+    /// created with the regions of a sequence format
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatRegion, MaxSeqErrors, SequenceErrors, SequenceFormat};
+    ///
+    /// let regions = FormatRegion::from_format_string("AGCTAGCTAG\n{8}\nTTGA\n{8}").unwrap();
+    /// let sequence_format = SequenceFormat::from_regions(&regions).unwrap();
+    /// let max_errors = MaxSeqErrors::new(None, None, None, vec![8, 8], None, 14, 0.0);
+    /// let mut sequence_errors = SequenceErrors::new_with_regions(&sequence_format, &max_errors);
+    /// sequence_errors.unknown_barcode(1, "GGGGGGGG");
+    /// assert_eq!(sequence_errors.unknown_barcodes()[0].total(), 0);
+    /// assert_eq!(sequence_errors.unknown_barcodes()[1].total(), 1);
+    /// ```
+    pub fn unknown_barcode(&mut self, barcode_index: usize, barcode: &str) {
+        if let Some(unknown_barcodes) = self.unknown_barcodes.get_mut(barcode_index) {
+            unknown_barcodes.add(barcode);
+        }
+    }
+
+    /// Returns the tally of raw counted barcodes that could not be fixed, for each counted barcode
+    pub fn unknown_barcodes(&self) -> &[BarcodeTally] {
+        &self.unknown_barcodes
+    }
+
+    /// Add one to the sample barcode matches with the number of mismatches
+    pub fn sample_barcode_match(&mut self, mismatches: u16) {
+        if let Some(sample_barcode_matches) = self.sample_barcode_matches.as_mut() {
+            sample_barcode_matches.matched(mismatches);
+        }
+    }
+
+    /// Add one to the matches of the counted barcode at barcode_index with the number of mismatches
+    pub fn barcode_match(&mut self, barcode_index: usize, mismatches: u16) {
+        if let Some(barcode_matches) = self.barcode_matches.get_mut(barcode_index) {
+            barcode_matches.matched(mismatches);
+        }
     }
 
     /// Add one to correct match
@@ -126,51 +432,970 @@ impl SequenceErrors {
         self.low_quality.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn arc_clone(&self) -> SequenceErrors {
-        SequenceErrors {
-            constant_region: Arc::clone(&self.constant_region),
-            sample_barcode: Arc::clone(&self.sample_barcode),
-            barcode: Arc::clone(&self.barcode),
-            matched: Arc::clone(&self.matched),
-            duplicates: Arc::clone(&self.duplicates),
-            low_quality: Arc::clone(&self.low_quality),
+    /// Add one to the match count of the strand the read was matched on
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{SequenceErrors, Strand};
+    ///
+    /// let mut sequence_errors = SequenceErrors::new();
+    /// sequence_errors.strand_match(&Strand::Reverse);
+    /// ```
+    pub fn strand_match(&mut self, strand: &Strand) {
+        match strand {
+            Strand::Reverse => self.reverse_strand.fetch_add(1, Ordering::Relaxed),
+            _ => self.forward_strand.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Create a new sequence error struct, with region counts for the sequence format, which shares the constant region error counts
+    /// with this one.  Used with multiple sequence formats, where a read that matches none of the formats cannot be assigned to any
+    /// single format
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatRegion, MaxSeqErrors, SequenceErrors, SequenceFormat};
+    ///
+    /// let regions = FormatRegion::from_format_string("AGCTAGCTAG\n{8}").unwrap();
+    /// let sequence_format = SequenceFormat::from_regions(&regions).unwrap();
+    /// let max_errors = MaxSeqErrors::new(None, None, None, vec![8], None, 10, 0.0);
+    /// let mut sequence_errors = SequenceErrors::new_with_regions(&sequence_format, &max_errors);
+    /// let second_format_errors = sequence_errors.share_constant_region(&sequence_format, &max_errors);
+    /// sequence_errors.constant_region_error();
+    /// ```
+    pub fn share_constant_region(
+        &self,
+        sequence_format: &SequenceFormat,
+        max_errors: &MaxSeqErrors,
+    ) -> SequenceErrors {
+        let mut sequence_errors = SequenceErrors::new_with_regions(sequence_format, max_errors);
+        sequence_errors.constant_region = Arc::clone(&self.constant_region);
+        sequence_errors.constant_region_ambiguous = Arc::clone(&self.constant_region_ambiguous);
+        sequence_errors.constant_region_matches.failures =
+            Arc::clone(&self.constant_region_matches.failures);
+        sequence_errors
+            .sample_reads
+            .share_constant_region(&self.sample_reads);
+        sequence_errors
+    }
+
+    pub fn arc_clone(&self) -> SequenceErrors {
+        SequenceErrors {
+            constant_region: Arc::clone(&self.constant_region),
+            sample_barcode: Arc::clone(&self.sample_barcode),
+            barcode: Arc::clone(&self.barcode),
+            constant_region_ambiguous: Arc::clone(&self.constant_region_ambiguous),
+            sample_barcode_ambiguous: Arc::clone(&self.sample_barcode_ambiguous),
+            barcode_ambiguous: Arc::clone(&self.barcode_ambiguous),
+            impossible_combination: Arc::clone(&self.impossible_combination),
+            matched: Arc::clone(&self.matched),
+            duplicates: Arc::clone(&self.duplicates),
+            low_quality: Arc::clone(&self.low_quality),
+            forward_strand: Arc::clone(&self.forward_strand),
+            reverse_strand: Arc::clone(&self.reverse_strand),
+            constant_region_matches: self.constant_region_matches.clone(),
+            sample_barcode_matches: self.sample_barcode_matches.clone(),
+            barcode_matches: self.barcode_matches.clone(),
+            constant_mismatch_profile: self.constant_mismatch_profile.clone(),
+            sample_reads: self.sample_reads.clone(),
+            undetermined_sample_barcodes: self.undetermined_sample_barcodes.clone(),
+            unknown_barcodes: self.unknown_barcodes.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SequenceErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\
+            Correctly matched sequences: {}\n\
+            Constant region mismatches:  {}\n\
+            Constant region ambiguous:   {}\n\
+            Sample barcode mismatches:   {}\n\
+            Sample barcode ambiguous:    {}\n\
+            Counted barcode mismatches:  {}\n\
+            Counted barcode ambiguous:   {}\n\
+            Impossible combinations:     {}\n\
+            Duplicates:                  {}\n\
+            Low quality barcodes:        {}\n\
+            Forward strand matches:      {}\n\
+            Reverse strand matches:      {}",
+            self.matched
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.constant_region
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.constant_region_ambiguous
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.sample_barcode
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.sample_barcode_ambiguous
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.barcode
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.barcode_ambiguous
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.impossible_combination
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.duplicates
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.low_quality
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.forward_strand
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en),
+            self.reverse_strand
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en)
+        )?;
+        // Add the matches of each region when they are counted
+        let region_matches = self.region_matches();
+        if !region_matches.is_empty() {
+            let name_width = region_matches
+                .iter()
+                .map(|region| region.name().chars().count())
+                .max()
+                .unwrap_or(0)
+                + 1;
+            write!(f, "\n\n-REGION MATCHES-")?;
+            for region in region_matches {
+                write!(
+                    f,
+                    "\n{:<width$} {}",
+                    format!("{}:", region.name()),
+                    region,
+                    width = name_width
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A snapshot of each error category count within SequenceErrors
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorCounts {
+    pub matched: u32,
+    pub constant_region_mismatches: u32,
+    pub constant_region_ambiguous: u32,
+    pub sample_barcode_mismatches: u32,
+    pub sample_barcode_ambiguous: u32,
+    pub counted_barcode_mismatches: u32,
+    pub counted_barcode_ambiguous: u32,
+    pub impossible_combinations: u32,
+    pub duplicates: u32,
+    pub low_quality: u32,
+    pub forward_strand: u32,
+    pub reverse_strand: u32,
+}
+
+/// Counts how reads matched a single region of the sequence format.  Reads that matched are counted by the number of
+/// mismatches, where 0 is an exact match, along with the reads where the region could not be matched
+#[derive(Debug, Clone)]
+pub struct RegionErrors {
+    name: String,                 // Region name used within the stats output
+    matches: Arc<Vec<AtomicU32>>, // Matched reads for each number of mismatches, from 0 to the maximum allowed
+    failures: Arc<AtomicU32>,     // Reads where the region did not match or the match was ambiguous
+}
+
+impl RegionErrors {
+    /// Creates a new region count with a count for each number of mismatches up to max_errors
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::RegionErrors;
+    ///
+    /// let mut region_errors = RegionErrors::new("Barcode_1", 1);
+    /// region_errors.matched(0);
+    /// region_errors.matched(1);
+    /// region_errors.failed();
+    /// assert_eq!(region_errors.to_string(), "Exact: 1  1 mismatch: 1  Failed: 1");
+    /// ```
+    pub fn new(name: &str, max_errors: u16) -> Self {
+        RegionErrors {
+            name: name.to_string(),
+            matches: Arc::new((0..=max_errors).map(|_| AtomicU32::new(0)).collect()),
+            failures: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Add one to the reads matched with the number of mismatches
+    pub fn matched(&mut self, mismatches: u16) {
+        if let Some(match_count) = self.matches.get(mismatches as usize) {
+            match_count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Add one to the reads where the region failed to match
+    pub fn failed(&mut self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the region name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the matched reads for each number of mismatches, starting with exact matches
+    pub fn match_counts(&self) -> Vec<u32> {
+        self.matches
+            .iter()
+            .map(|match_count| match_count.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /// Returns the reads where the region failed to match
+    pub fn failures(&self) -> u32 {
+        self.failures.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for RegionErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts = Vec::new();
+        for (mismatches, match_count) in self.matches.iter().enumerate() {
+            let label = match mismatches {
+                0 => "Exact".to_string(),
+                1 => "1 mismatch".to_string(),
+                _ => format!("{} mismatches", mismatches),
+            };
+            counts.push(format!(
+                "{}: {}",
+                label,
+                match_count
+                    .load(Ordering::Relaxed)
+                    .to_formatted_string(&Locale::en)
+            ));
+        }
+        counts.push(format!(
+            "Failed: {}",
+            self.failures
+                .load(Ordering::Relaxed)
+                .to_formatted_string(&Locale::en)
+        ));
+        write!(f, "{}", counts.join("  "))
+    }
+}
+
+/// Counts where constant region mismatches fall along the format string, over the reads that needed their constant
+/// region corrected
+#[derive(Debug, Clone)]
+pub struct MismatchProfile {
+    corrected: Arc<AtomicU32>,      // Reads with a corrected constant region
+    positions: Arc<Vec<AtomicU32>>, // Mismatches at each position of the format string
+}
+
+impl MismatchProfile {
+    /// Creates an empty profile with a count for each position of a format string of format_length
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::MismatchProfile;
+    ///
+    /// let mut mismatch_profile = MismatchProfile::new(8);
+    /// mismatch_profile.add_corrected(&[2, 5]);
+    /// mismatch_profile.add_corrected(&[2]);
+    /// assert_eq!(mismatch_profile.corrected(), 2);
+    /// assert_eq!(mismatch_profile.position_counts(), vec![0, 0, 2, 0, 0, 1, 0, 0]);
+    /// ```
+    pub fn new(format_length: usize) -> Self {
+        MismatchProfile {
+            corrected: Arc::new(AtomicU32::new(0)),
+            positions: Arc::new((0..format_length).map(|_| AtomicU32::new(0)).collect()),
+        }
+    }
+
+    /// Adds a corrected read along with the format string positions of its mismatches
+    pub fn add_corrected(&mut self, mismatch_positions: &[usize]) {
+        self.corrected.fetch_add(1, Ordering::Relaxed);
+        for position in mismatch_positions {
+            if let Some(position_count) = self.positions.get(*position) {
+                position_count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns the number of reads with a corrected constant region
+    pub fn corrected(&self) -> u32 {
+        self.corrected.load(Ordering::Relaxed)
+    }
+
+    /// Returns the mismatch count for each position of the format string
+    pub fn position_counts(&self) -> Vec<u32> {
+        self.positions
+            .iter()
+            .map(|position_count| position_count.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+/// The outcome of a single read, used to count reads for each sample barcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadOutcome {
+    Matched,
+    Duplicate,
+    LowQuality,
+    ConstantRegionError,
+    SampleBarcodeError,
+    CountedBarcodeError,
+    ImpossibleCombination,
+}
+
+/// Read outcome counts for a single sample barcode, or for the undetermined reads
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SampleReadCounts {
+    pub matched: u32,
+    pub duplicates: u32,
+    pub low_quality: u32,
+    pub constant_region_errors: u32,
+    pub sample_barcode_errors: u32,
+    pub counted_barcode_errors: u32,
+    pub impossible_combinations: u32,
+}
+
+impl SampleReadCounts {
+    /// Add one to the count of the read outcome
+    pub fn add(&mut self, outcome: ReadOutcome) {
+        let count = match outcome {
+            ReadOutcome::Matched => &mut self.matched,
+            ReadOutcome::Duplicate => &mut self.duplicates,
+            ReadOutcome::LowQuality => &mut self.low_quality,
+            ReadOutcome::ConstantRegionError => &mut self.constant_region_errors,
+            ReadOutcome::SampleBarcodeError => &mut self.sample_barcode_errors,
+            ReadOutcome::CountedBarcodeError => &mut self.counted_barcode_errors,
+            ReadOutcome::ImpossibleCombination => &mut self.impossible_combinations,
+        };
+        *count += 1;
+    }
+
+    /// Returns the total number of reads with any outcome
+    pub fn total(&self) -> u32 {
+        self.matched
+            + self.duplicates
+            + self.low_quality
+            + self.constant_region_errors
+            + self.sample_barcode_errors
+            + self.counted_barcode_errors
+            + self.impossible_combinations
+    }
+}
+
+/// Read outcome counts for a single sample barcode which can be added to from multiple threads
+#[derive(Debug, Default)]
+struct AtomicSampleReadCounts {
+    counts: [AtomicU32; 7], // Counts in the order of the ReadOutcome variants
+}
+
+impl AtomicSampleReadCounts {
+    /// Add one to the count of the read outcome
+    fn add(&self, outcome: ReadOutcome) {
+        self.counts[outcome as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the current counts
+    fn load(&self) -> SampleReadCounts {
+        let count = |outcome: ReadOutcome| self.counts[outcome as usize].load(Ordering::Relaxed);
+        SampleReadCounts {
+            matched: count(ReadOutcome::Matched),
+            duplicates: count(ReadOutcome::Duplicate),
+            low_quality: count(ReadOutcome::LowQuality),
+            constant_region_errors: count(ReadOutcome::ConstantRegionError),
+            sample_barcode_errors: count(ReadOutcome::SampleBarcodeError),
+            counted_barcode_errors: count(ReadOutcome::CountedBarcodeError),
+            impossible_combinations: count(ReadOutcome::ImpossibleCombination),
+        }
+    }
+}
+
+/// Counts the outcome of the reads for each sample barcode.  Reads which fail before the sample barcode is known, such
+/// as constant region and sample barcode errors, are counted as undetermined
+#[derive(Debug, Clone, Default)]
+pub struct SampleReads {
+    known: Arc<HashMap<String, AtomicSampleReadCounts>>, // Read counts of the sample barcodes known before any reads are parsed
+    unknown: Arc<Mutex<HashMap<String, SampleReadCounts>>>, // Read counts of any other sample barcode, used when there is no sample barcode file
+    undetermined: Arc<AtomicSampleReadCounts>, // Read counts of the reads without a sample barcode
+    constant_region_errors: Arc<AtomicU32>, // Undetermined constant region errors, which can be shared between sequence formats
+}
+
+impl SampleReads {
+    /// Creates an empty count of read outcomes
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{ReadOutcome, SampleReads};
+    ///
+    /// let mut sample_reads = SampleReads::new();
+    /// sample_reads.add(Some("AGCTAGCT"), ReadOutcome::Matched);
+    /// sample_reads.add(Some("AGCTAGCT"), ReadOutcome::LowQuality);
+    /// sample_reads.add(None, ReadOutcome::SampleBarcodeError);
+    /// let sample_counts = sample_reads.sample_counts();
+    /// assert_eq!(sample_counts[0].0, "AGCTAGCT");
+    /// assert_eq!(sample_counts[0].1.total(), 2);
+    /// assert_eq!(sample_reads.undetermined().sample_barcode_errors, 1);
+    /// ```
+    pub fn new() -> Self {
+        SampleReads {
+            known: Arc::new(HashMap::new()),
+            unknown: Arc::new(Mutex::new(HashMap::new())),
+            undetermined: Arc::new(AtomicSampleReadCounts::default()),
+            constant_region_errors: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Shares the undetermined constant region errors with another count of read outcomes.  Used with multiple sequence formats, where a
+    /// read that matches none of the formats cannot be assigned to any single format
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{ReadOutcome, SampleReads};
+    ///
+    /// let mut sample_reads = SampleReads::new();
+    /// let mut second_format_reads = SampleReads::new();
+    /// second_format_reads.share_constant_region(&sample_reads);
+    /// sample_reads.add(None, ReadOutcome::ConstantRegionError);
+    /// second_format_reads.add(None, ReadOutcome::SampleBarcodeError);
+    /// assert_eq!(second_format_reads.undetermined().constant_region_errors, 1);
+    /// assert_eq!(sample_reads.undetermined().sample_barcode_errors, 0);
+    /// ```
+    pub fn share_constant_region(&mut self, sample_reads: &SampleReads) {
+        self.constant_region_errors = Arc::clone(&sample_reads.constant_region_errors);
+    }
+
+    /// Sets the sample barcodes that are known before any reads are parsed, so that their reads are counted without locking.  These
+    /// are the sample barcodes within the sample barcode file, or 'barcode' when the format does not have a sample barcode
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{ReadOutcome, SampleReads};
+    ///
+    /// let samples_barcode_hash = [("AGCTAGCT".to_string(), "Sample_1".to_string())].into_iter().collect();
+    /// let mut sample_reads = SampleReads::new();
+    /// sample_reads.set_sample_barcodes(&samples_barcode_hash, true);
+    /// sample_reads.add(Some("AGCTAGCT"), ReadOutcome::Matched);
+    /// sample_reads.add(Some("TTTTTTTT"), ReadOutcome::Matched);
+    /// assert_eq!(sample_reads.sample_counts().len(), 2);
+    /// ```
+    pub fn set_sample_barcodes(
+        &mut self,
+        samples_barcode_hash: &HashMap<String, String>,
+        sample_barcode: bool,
+    ) {
+        let known = if sample_barcode {
+            samples_barcode_hash
+                .keys()
+                .map(|sample_barcode| (sample_barcode.clone(), AtomicSampleReadCounts::default()))
+                .collect()
+        } else {
+            [("barcode".to_string(), AtomicSampleReadCounts::default())]
+                .into_iter()
+                .collect()
+        };
+        self.known = Arc::new(known);
+    }
+
+    /// Add one to the read outcome of the sample barcode, or to the undetermined reads with None
+    pub fn add(&mut self, sample_barcode_option: Option<&str>, outcome: ReadOutcome) {
+        match sample_barcode_option {
+            Some(sample_barcode) => {
+                if let Some(counts) = self.known.get(sample_barcode) {
+                    counts.add(outcome);
+                } else {
+                    // Only allocate the sample barcode the first time it is seen
+                    let mut unknown = self.unknown.lock().unwrap();
+                    if let Some(counts) = unknown.get_mut(sample_barcode) {
+                        counts.add(outcome);
+                    } else {
+                        let mut counts = SampleReadCounts::default();
+                        counts.add(outcome);
+                        unknown.insert(sample_barcode.to_string(), counts);
+                    }
+                }
+            }
+            None if outcome == ReadOutcome::ConstantRegionError => {
+                self.constant_region_errors.fetch_add(1, Ordering::Relaxed);
+            }
+            None => self.undetermined.add(outcome),
+        }
+    }
+
+    /// Returns the read counts of each sample barcode with any reads, sorted by sample barcode
+    pub fn sample_counts(&self) -> Vec<(String, SampleReadCounts)> {
+        let unknown = self.unknown.lock().unwrap();
+        self.known
+            .iter()
+            .map(|(sample_barcode, counts)| (sample_barcode.clone(), counts.load()))
+            .filter(|(_, counts)| counts.total() > 0)
+            .chain(
+                unknown
+                    .iter()
+                    .map(|(sample_barcode, counts)| (sample_barcode.clone(), *counts)),
+            )
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .collect()
+    }
+
+    /// Returns the read counts of the reads that failed before the sample barcode was known
+    pub fn undetermined(&self) -> SampleReadCounts {
+        let mut undetermined = self.undetermined.load();
+        undetermined.constant_region_errors = self.constant_region_errors.load(Ordering::Relaxed);
+        undetermined
+    }
+}
+
+/// The most raw sequences held by a barcode tally.  Reads of new sequences after this are only added to the total
+const MAX_TALLIED_SEQUENCES: usize = 100_000;
+
+/// Tallies the raw sequences of a region that could not be matched to a known barcode.  Holds up to 100,000 distinct sequences so that
+/// a run with many failed reads does not use up the memory, which is plenty to find the most frequent ones
+#[derive(Debug, Clone, Default)]
+pub struct BarcodeTally {
+    counts: Arc<Mutex<HashMap<String, u32>>>, // Number of reads for each raw sequence
+    untallied: Arc<AtomicU32>,                // Reads of sequences seen after the tally was full
+}
+
+impl BarcodeTally {
+    /// Creates an empty tally
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::BarcodeTally;
+    ///
+    /// let mut barcode_tally = BarcodeTally::new();
+    /// barcode_tally.add("AGCT");
+    /// barcode_tally.add("TTTT");
+    /// barcode_tally.add("TTTT");
+    /// assert_eq!(barcode_tally.total(), 3);
+    /// assert_eq!(barcode_tally.top(1), vec![("TTTT".to_string(), 2)]);
+    /// ```
+    pub fn new() -> Self {
+        BarcodeTally {
+            counts: Arc::new(Mutex::new(HashMap::new())),
+            untallied: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Add one to the count of the sequence.  The sequence is only copied the first time it is seen
+    pub fn add(&mut self, sequence: &str) {
+        let mut counts = self.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(sequence) {
+            *count += 1;
+        } else if counts.len() < MAX_TALLIED_SEQUENCES {
+            counts.insert(sequence.to_string(), 1);
+        } else {
+            self.untallied.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the total number of reads added, including those of sequences seen after the tally was full
+    pub fn total(&self) -> u32 {
+        self.counts.lock().unwrap().values().sum::<u32>() + self.untallied.load(Ordering::Relaxed)
+    }
+
+    /// Returns up to top_number sequences with the most reads, sorted by count and then by sequence
+    pub fn top(&self, top_number: usize) -> Vec<(String, u32)> {
+        self.counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(sequence, count)| (sequence.clone(), *count))
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .take(top_number)
+            .collect()
+    }
+}
+
+/// Which strand of each read is searched for the sequence format.  With 'Both', the reverse
+/// complement is only searched when the forward read fails to match the constant region
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strand {
+    Forward,
+    Reverse,
+    Both,
+}
+
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strand = match self {
+            Strand::Forward => "forward",
+            Strand::Reverse => "reverse",
+            Strand::Both => "both",
+        };
+        write!(f, "{}", strand)
+    }
+}
+
+/// The order rows are written within the counts files.  'Count' is from most to least counted, 'Id' is by the written
+/// barcode IDs, and 'Sequence' is by the DNA barcodes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Count,
+    Id,
+    Sequence,
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sort_order = match self {
+            SortOrder::Count => "count",
+            SortOrder::Id => "id",
+            SortOrder::Sequence => "sequence",
+        };
+        write!(f, "{}", sort_order)
+    }
+}
+
+/// Compression used for the counts files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the file extension added to compressed file names
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::Compression;
+    ///
+    /// assert_eq!(Compression::Zstd.extension(), ".zst");
+    /// ```
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compression = match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        };
+        write!(f, "{}", compression)
+    }
+}
+
+/// The type of each region within the sequence format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionType {
+    Sample,
+    Barcode,
+    Random,
+    Constant,
+}
+
+impl fmt::Display for RegionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let region_type = match self {
+            RegionType::Sample => "sample",
+            RegionType::Barcode => "barcode",
+            RegionType::Random => "random",
+            RegionType::Constant => "constant",
+        };
+        write!(f, "{}", region_type)
+    }
+}
+
+/// A single region of the sequence format.  Created from either format file syntax.  The optional settings can only be
+/// set with the structured (TOML) format file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormatRegion {
+    #[serde(rename = "type")]
+    pub region_type: RegionType,
+    pub length: Option<u16>, // Barcode length.  Not used for constant regions
+    pub sequence: Option<String>, // Constant region nucleotides.  Only used for constant regions
+    pub name: Option<String>, // Column name used for counted barcodes within the output files
+    pub max_errors: Option<u16>, // Maximum sequencing errors allowed within the region
+    pub min_quality: Option<f32>, // Minimum average read quality score allowed within the region
+    pub conversion: Option<String>, // Barcode conversion file for sample or counted barcode regions
+}
+
+impl FormatRegion {
+    /// Creates a region with only the type, length, and constant sequence set
+    pub fn new(region_type: RegionType, length: Option<u16>, sequence: Option<String>) -> Self {
+        FormatRegion {
+            region_type,
+            length,
+            sequence,
+            name: None,
+            max_errors: None,
+            min_quality: None,
+            conversion: None,
+        }
+    }
+
+    /// Parses a structured (TOML) format file into regions.  Each region is a '[[region]]' table, in the order they occur
+    /// within the read
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatRegion, RegionType};
+    ///
+    /// let format_data = "
+    /// [[region]]
+    /// type = \"constant\"
+    /// sequence = \"AGCT\"
+    ///
+    /// [[region]]
+    /// type = \"barcode\"
+    /// name = \"cycle_A\"
+    /// length = 6
+    /// max_errors = 1
+    /// ";
+    /// let regions = FormatRegion::from_toml(format_data).unwrap();
+    /// assert_eq!(regions.len(), 2);
+    /// assert_eq!(regions[1].region_type, RegionType::Barcode);
+    /// assert_eq!(regions[1].name, Some("cycle_A".to_string()));
+    /// ```
+    pub fn from_toml(format_data: &str) -> Result<Vec<Self>> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct StructuredFormat {
+            region: Vec<FormatRegion>,
+        }
+        let structured_format: StructuredFormat =
+            toml::from_str(format_data).context("Failed to parse the structured format file")?;
+        Ok(structured_format.region)
+    }
+
+    /// Parses the original format file syntax, made of '[#]', '{#}', '(#)' and constant nucleotides, into regions.
+    /// Whitespace is ignored and lines starting with '#' are comments.  Any other character is reported as an error
+    /// with its line and column
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatError, FormatRegion, RegionType};
+    ///
+    /// let regions = FormatRegion::from_format_string("[8]\nAGCT\n{6}").unwrap();
+    /// assert_eq!(regions.len(), 3);
+    /// assert_eq!(regions[0].region_type, RegionType::Sample);
+    /// assert_eq!(regions[2].length, Some(6));
+    ///
+    /// let error = FormatRegion::from_format_string("[8]\nAGCT\n{6").unwrap_err();
+    /// assert_eq!(error, FormatError::UnbalancedBracket { line: 3, column: 1, bracket: '{' });
+    /// ```
+    pub fn from_format_string(format_data: &str) -> std::result::Result<Vec<Self>, FormatError> {
+        let mut regions: Vec<FormatRegion> = Vec::new();
+        for (line_index, line) in format_data.lines().enumerate() {
+            // Skip any line that starts with '#'
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+            let line_num = line_index + 1;
+            let line_chars = line.chars().collect::<Vec<char>>();
+            let mut char_index = 0;
+            while char_index < line_chars.len() {
+                let column = char_index + 1;
+                let current_char = line_chars[char_index];
+                let (region_type, close_bracket) = match current_char {
+                    '[' => (RegionType::Sample, ']'),
+                    '{' => (RegionType::Barcode, '}'),
+                    '(' => (RegionType::Random, ')'),
+                    ']' | '}' | ')' => {
+                        return Err(FormatError::UnbalancedBracket {
+                            line: line_num,
+                            column,
+                            bracket: current_char,
+                        })
+                    }
+                    whitespace if whitespace.is_whitespace() => {
+                        char_index += 1;
+                        continue;
+                    }
+                    nucleotide if "ATGCN".contains(nucleotide.to_ascii_uppercase()) => {
+                        // Constant nucleotides without a barcode between them are kept as one constant region
+                        let nucleotide = nucleotide.to_ascii_uppercase();
+                        match regions.last_mut() {
+                            Some(FormatRegion {
+                                region_type: RegionType::Constant,
+                                sequence: Some(sequence),
+                                ..
+                            }) => sequence.push(nucleotide),
+                            _ => regions.push(FormatRegion::new(
+                                RegionType::Constant,
+                                None,
+                                Some(nucleotide.to_string()),
+                            )),
+                        }
+                        char_index += 1;
+                        continue;
+                    }
+                    token => {
+                        return Err(FormatError::UnknownToken {
+                            line: line_num,
+                            column,
+                            token,
+                        })
+                    }
+                };
+
+                // The closing bracket needs to be on the same line with no other bracket before it
+                let close_index = line_chars[char_index + 1..]
+                    .iter()
+                    .position(|bracket| "[]{}()".contains(*bracket))
+                    .map(|offset| char_index + 1 + offset)
+                    .filter(|close_index| line_chars[*close_index] == close_bracket)
+                    .ok_or(FormatError::UnbalancedBracket {
+                        line: line_num,
+                        column,
+                        bracket: current_char,
+                    })?;
+                let length_str = line_chars[char_index + 1..close_index]
+                    .iter()
+                    .collect::<String>();
+                let length = length_str
+                    .chars()
+                    .all(|digit| digit.is_ascii_digit())
+                    .then(|| length_str.parse::<u16>().ok())
+                    .flatten()
+                    .ok_or_else(|| FormatError::InvalidLength {
+                        line: line_num,
+                        column,
+                        length: length_str.clone(),
+                    })?;
+                if length == 0 {
+                    return Err(FormatError::ZeroLength {
+                        line: line_num,
+                        column,
+                    });
+                }
+                // Only one sample and one random barcode can be handled
+                if region_type != RegionType::Barcode
+                    && regions
+                        .iter()
+                        .any(|region| region.region_type == region_type)
+                {
+                    return Err(FormatError::DuplicateRegion {
+                        line: line_num,
+                        column,
+                        region_type,
+                    });
+                }
+                regions.push(FormatRegion::new(region_type, Some(length), None));
+                char_index = close_index + 1;
+            }
+        }
+
+        if regions.is_empty() {
+            return Err(FormatError::EmptyFormat);
+        }
+        if !regions
+            .iter()
+            .any(|region| region.region_type == RegionType::Barcode)
+        {
+            return Err(FormatError::MissingBarcode);
         }
+        Ok(regions)
     }
 }
 
-impl fmt::Display for SequenceErrors {
+/// Errors found while validating a format file.  Lines and columns start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    UnknownToken {
+        line: usize,
+        column: usize,
+        token: char,
+    },
+    UnbalancedBracket {
+        line: usize,
+        column: usize,
+        bracket: char,
+    },
+    InvalidLength {
+        line: usize,
+        column: usize,
+        length: String,
+    },
+    ZeroLength {
+        line: usize,
+        column: usize,
+    },
+    DuplicateRegion {
+        line: usize,
+        column: usize,
+        region_type: RegionType,
+    },
+    EmptyFormat,
+    MissingBarcode,
+    InvalidRegion {
+        region: usize, // Region number within the structured format file
+        reason: String,
+    },
+}
+
+impl fmt::Display for FormatError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "\
-            Correctly matched sequences: {}\n\
-            Constant region mismatches:  {}\n\
-            Sample barcode mismatches:   {}\n\
-            Counted barcode mismatches:  {}\n\
-            Duplicates:                  {}\n\
-            Low quality barcodes:        {}",
-            self.matched
-                .load(Ordering::Relaxed)
-                .to_formatted_string(&Locale::en),
-            self.constant_region
-                .load(Ordering::Relaxed)
-                .to_formatted_string(&Locale::en),
-            self.sample_barcode
-                .load(Ordering::Relaxed)
-                .to_formatted_string(&Locale::en),
-            self.barcode
-                .load(Ordering::Relaxed)
-                .to_formatted_string(&Locale::en),
-            self.duplicates
-                .load(Ordering::Relaxed)
-                .to_formatted_string(&Locale::en),
-            self.low_quality
-                .load(Ordering::Relaxed)
-                .to_formatted_string(&Locale::en)
-        )
+        match self {
+            FormatError::UnknownToken {
+                line,
+                column,
+                token,
+            } => write!(
+                f,
+                "Line {}, column {}: unknown character '{}'.  Only A, G, C, T, N, [#], {{#}}, and (#) are allowed",
+                line, column, token
+            ),
+            FormatError::UnbalancedBracket {
+                line,
+                column,
+                bracket,
+            } => write!(
+                f,
+                "Line {}, column {}: unbalanced bracket '{}'",
+                line, column, bracket
+            ),
+            FormatError::InvalidLength {
+                line,
+                column,
+                length,
+            } => write!(
+                f,
+                "Line {}, column {}: '{}' is not a valid barcode length",
+                line, column, length
+            ),
+            FormatError::ZeroLength { line, column } => write!(
+                f,
+                "Line {}, column {}: barcode length cannot be 0",
+                line, column
+            ),
+            FormatError::DuplicateRegion {
+                line,
+                column,
+                region_type,
+            } => write!(
+                f,
+                "Line {}, column {}: only one {} barcode is allowed",
+                line, column, region_type
+            ),
+            FormatError::EmptyFormat => write!(f, "The format file contains no regions"),
+            FormatError::MissingBarcode => {
+                write!(f, "The format file needs at least one counted barcode")
+            }
+            FormatError::InvalidRegion { region, reason } => {
+                write!(f, "Region {}: {}", region, reason)
+            }
+        }
     }
 }
 
+impl std::error::Error for FormatError {}
+
 // Struct to keep the format information for the sequencing, ie barcodes, regex search etc.
 #[derive(Debug, Clone)]
 pub struct SequenceFormat {
@@ -184,6 +1409,13 @@ pub struct SequenceFormat {
     pub sample_length_option: Option<u16>, // Sample barcode length
     pub random_barcode: bool,        // Whether a random barcode is included
     pub sample_barcode: bool,        // Whether a sammple barcode is included
+    pub barcode_names: Vec<String>,  // Output column names of the counted barcodes
+    pub sample_max_errors: Option<u16>, // Sample barcode errors allowed from the format file
+    pub barcode_max_errors: Vec<Option<u16>>, // Errors allowed for each counted barcode from the format file
+    pub constant_max_errors: Option<u16>,     // Constant region errors allowed from the format file
+    pub region_min_qualities: Vec<(usize, usize, f32)>, // (start, end, minimum average quality) for regions with their own quality threshold
+    pub sample_conversion_option: Option<String>,       // Sample barcode file from the format file
+    pub barcode_conversions: Vec<Option<String>>, // Barcode conversion file for each counted barcode from the format file
 }
 
 impl SequenceFormat {
@@ -208,101 +1440,206 @@ impl SequenceFormat {
             sample_length_option: None,
             random_barcode: false,
             sample_barcode: false,
+            barcode_names: Vec::new(),
+            sample_max_errors: None,
+            barcode_max_errors: Vec::new(),
+            constant_max_errors: None,
+            region_min_qualities: Vec::new(),
+            sample_conversion_option: None,
+            barcode_conversions: Vec::new(),
         })
     }
     /// Parses the format file into all fields of the SequenceFormat struct, including the regex
-    /// search, barcode sizes, and sequence format strings.
+    /// search, barcode sizes, and sequence format strings.  Files ending in '.toml' are read as a
+    /// structured format file, otherwise the original format syntax is used
     pub fn parse_format_file(format_path: &str) -> Result<Self> {
-        let mut sequence_format = SequenceFormat::new()?;
         // Read sequence format file to string
-        let format_data = fs::read_to_string(format_path)
-            .context(format!("Failed to open {}", format_path))?
-            .lines() // split into lines
-            .filter(|line| !line.starts_with('#')) // remove any line that starts with '#'
-            .collect::<String>(); // collect into a String
+        let format_data =
+            fs::read_to_string(format_path).context(format!("Failed to open {}", format_path))?;
+        let mut regions = if format_path.ends_with(".toml") {
+            FormatRegion::from_toml(&format_data)
+        } else {
+            FormatRegion::from_format_string(&format_data).map_err(anyhow::Error::from)
+        }
+        .context(format!("Failed to parse format file {}", format_path))?;
 
+        // Conversion files within the format file are relative to the format file
+        let format_directory = Path::new(format_path).parent().unwrap_or(Path::new(""));
+        for region in regions.iter_mut() {
+            if let Some(conversion) = region.conversion.as_mut() {
+                if Path::new(conversion).is_relative() {
+                    *conversion = format_directory
+                        .join(&conversion)
+                        .to_string_lossy()
+                        .to_string();
+                }
+            }
+        }
+        SequenceFormat::from_regions(&regions)
+            .context(format!("Failed to parse format file {}", format_path))
+    }
+
+    /// Creates the SequenceFormat from the regions of the sequence format, in the order they occur within the read
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatRegion, SequenceFormat};
+    ///
+    /// let regions = FormatRegion::from_format_string("[8]\nAGCT\n{6}\nTTGA\n{6}").unwrap();
+    /// let sequence_format = SequenceFormat::from_regions(&regions).unwrap();
+    /// assert_eq!(sequence_format.format_string, "NNNNNNNNAGCTNNNNNNTTGANNNNNN");
+    /// assert_eq!(sequence_format.barcode_lengths, vec![6, 6]);
+    /// assert_eq!(sequence_format.barcode_names, vec!["Barcode_1", "Barcode_2"]);
+    /// assert_eq!(sequence_format.constant_region_length, 8);
+    /// ```
+    pub fn from_regions(regions: &[FormatRegion]) -> Result<Self> {
+        let mut sequence_format = SequenceFormat::new()?;
         // Starts the string that is used to create the regex search
         let mut regex_string = String::new();
-        // Digit search to find the number within any format group
-        let digit_search = Regex::new(r"\d+")?;
-        // Search groups separated by '|' or statements in order to iterate through each group
-        // within the format data from the format file and create the regex search string, along
-        // with add the other needed information.  Uses the {#}, [#], (#), [ATGC], and 'N's as
-        // groups
-        let barcode_search = Regex::new(r"(?i)(\{\d+\})|(\[\d+\])|(\(\d+\))|N+|[ATGC]+")?;
-        for group in barcode_search.find_iter(&format_data) {
-            let group_str = group.as_str();
-            // Holds the capture group name.  Is non-barcode regions
-            let mut group_name_option = None;
-
-            // If the group is a barcode group, add the capture group name, and set barcode
-            // included fields to true
-            if group_str.contains('[') {
-                group_name_option = Some("sample".to_string());
-                sequence_format.sample_barcode = true;
-            } else if group_str.contains('{') {
-                sequence_format.barcode_num += 1;
-                group_name_option = Some(format!("barcode{}", sequence_format.barcode_num));
-            } else if group_str.contains('(') {
-                group_name_option = Some("random".to_string());
-                sequence_format.random_barcode = true;
-            }
-
-            if let Some(group_name) = group_name_option {
-                let digits = digit_search
-                    .captures(group_str)
-                    .unwrap()
-                    .get(0)
-                    .unwrap()
-                    .as_str()
-                    .parse::<u16>()
-                    .unwrap();
-
-                // Create the capture group with the group name for the barcode and add it to the
-                // string created for the regex search
-                let mut capture_group = format!("(?P<{}>.", group_name);
-                capture_group.push('{');
-                capture_group.push_str(&digits.to_string());
-                capture_group.push_str("})");
-                regex_string.push_str(&capture_group);
-
-                // Add lengths of any of the barcodes to the sequence_format struct fields.  Also
-                // set the code for the regions_string
-                let mut push_char = '\0';
-                if group_name == "sample" {
+        let mut constant_errors_set = false;
+        let mut constant_max_errors = 0;
+        let barcode_regions = regions
+            .iter()
+            .filter(|region| region.region_type == RegionType::Barcode)
+            .count();
+        if regions.is_empty() {
+            return Err(FormatError::EmptyFormat.into());
+        }
+        if barcode_regions == 0 {
+            return Err(FormatError::MissingBarcode.into());
+        }
+        for (region_index, region) in regions.iter().enumerate() {
+            let invalid_region = |reason: &str| FormatError::InvalidRegion {
+                region: region_index + 1,
+                reason: reason.to_string(),
+            };
+            if region.region_type == RegionType::Constant {
+                let sequence = region
+                    .sequence
+                    .as_ref()
+                    .ok_or_else(|| invalid_region("constant region is missing its sequence"))?
+                    .to_uppercase();
+                if sequence.is_empty()
+                    || sequence
+                        .chars()
+                        .any(|nucleotide| !"ATGCN".contains(nucleotide))
+                {
+                    return Err(invalid_region(&format!(
+                        "constant sequence '{}' can only contain A, G, C, T, or N",
+                        sequence
+                    ))
+                    .into());
+                }
+                // Any A,G,C, or T is treated as constant region here.  'N's are treated as 'any' nucleotide for error
+                // handling and matching
+                for (is_n, group) in &sequence.chars().group_by(|nucleotide| *nucleotide == 'N') {
+                    let group_str = group.collect::<String>();
+                    let group_length = group_str.chars().count();
+                    if is_n {
+                        regex_string.push_str(&format!("[AGCT]{{{}}}", group_length));
+                    } else {
+                        regex_string.push_str(&group_str);
+                        sequence_format.constant_region_length += group_length as u16;
+                    }
+                    sequence_format.format_string.push_str(&group_str);
+                    for _ in 0..group_length {
+                        sequence_format.regions_string.push('C');
+                    }
+                }
+                let constant_length = sequence
+                    .chars()
+                    .filter(|nucleotide| *nucleotide != 'N')
+                    .count() as u16;
+                if let Some(max_errors) = region.max_errors {
+                    constant_errors_set = true;
+                    constant_max_errors += max_errors;
+                } else {
+                    constant_max_errors += constant_length / 5;
+                }
+                continue;
+            }
+
+            let digits = region.length.ok_or_else(|| {
+                invalid_region(&format!(
+                    "{} region is missing its length",
+                    region.region_type
+                ))
+            })?;
+            if digits == 0 {
+                return Err(invalid_region("barcode length cannot be 0").into());
+            }
+            // Only one sample and one random barcode can be handled
+            if region.region_type != RegionType::Barcode
+                && regions[..region_index]
+                    .iter()
+                    .any(|previous| previous.region_type == region.region_type)
+            {
+                return Err(invalid_region(&format!(
+                    "only one {} barcode is allowed",
+                    region.region_type
+                ))
+                .into());
+            }
+            // Holds the capture group name and sets barcode included fields to true
+            let group_name;
+            let push_char;
+            match region.region_type {
+                RegionType::Sample => {
+                    group_name = "sample".to_string();
+                    push_char = 'S';
+                    sequence_format.sample_barcode = true;
                     sequence_format.sample_length_option = Some(digits);
-                    push_char = 'S'
-                } else if group_name.contains("barcode") {
-                    sequence_format.barcode_lengths.push(digits);
-                    push_char = 'B'
-                } else if group_name == "random" {
-                    push_char = 'R'
+                    sequence_format.sample_max_errors = region.max_errors;
+                    sequence_format.sample_conversion_option = region.conversion.clone();
                 }
-                // For the number of nucleotides of the barcode add 'N's to format string and the
-                // push_char just set to regions_string
-                for _ in 0..digits {
-                    sequence_format.regions_string.push(push_char);
-                    sequence_format.format_string.push('N')
+                RegionType::Barcode => {
+                    sequence_format.barcode_num += 1;
+                    group_name = format!("barcode{}", sequence_format.barcode_num);
+                    push_char = 'B';
+                    sequence_format.barcode_lengths.push(digits);
+                    sequence_format.barcode_max_errors.push(region.max_errors);
+                    sequence_format
+                        .barcode_conversions
+                        .push(region.conversion.clone());
+                    let barcode_name = match &region.name {
+                        Some(name) => name.clone(),
+                        None if barcode_regions > 1 => {
+                            format!("Barcode_{}", sequence_format.barcode_num)
+                        }
+                        None => "Barcode".to_string(),
+                    };
+                    sequence_format.barcode_names.push(barcode_name);
                 }
-            } else if group_str.contains('N') {
-                // Used to handle if 'N's are added to the format file.  These will be treated as
-                // 'any' nucleotide for error handling and matching
-                let num_of_ns = group_str.matches('N').count();
-                let mut n_group = "[AGCT]{".to_string();
-                n_group.push_str(&num_of_ns.to_string());
-                n_group.push('}');
-                regex_string.push_str(&n_group);
-                sequence_format.format_string.push_str(group_str);
-            } else {
-                // Any A,G,C, or T is treated as constant region here
-                regex_string.push_str(&group_str.to_uppercase());
-                sequence_format.format_string.push_str(group_str);
-                let constant_group_length = group_str.chars().count();
-                for _ in 0..constant_group_length {
-                    sequence_format.regions_string.push('C');
+                _ => {
+                    group_name = "random".to_string();
+                    push_char = 'R';
+                    sequence_format.random_barcode = true;
                 }
-                sequence_format.constant_region_length += constant_group_length as u16;
             }
+
+            // Record the quality threshold for the region if one was set
+            if let Some(min_quality) = region.min_quality {
+                let start = sequence_format.format_string.chars().count();
+                sequence_format.region_min_qualities.push((
+                    start,
+                    start + digits as usize,
+                    min_quality,
+                ));
+            }
+
+            // Create the capture group with the group name for the barcode and add it to the
+            // string created for the regex search
+            regex_string.push_str(&format!("(?P<{}>.{{{}}})", group_name, digits));
+
+            // For the number of nucleotides of the barcode add 'N's to format string and the
+            // push_char just set to regions_string
+            for _ in 0..digits {
+                sequence_format.regions_string.push(push_char);
+                sequence_format.format_string.push('N')
+            }
+        }
+        if constant_errors_set {
+            sequence_format.constant_max_errors = Some(constant_max_errors);
         }
         sequence_format.length = sequence_format.format_string.chars().count();
         sequence_format.format_regex = Regex::new(&regex_string)?;
@@ -340,6 +1677,12 @@ pub struct BarcodeConversions {
     pub sample_seqs: AHashSet<String>,
     pub counted_barcodes_hash: Vec<HashMap<String, String>>,
     pub counted_barcode_seqs: Vec<AHashSet<String>>,
+    pub expected_combinations: Vec<String>, // comma separated counted barcode sequences of every library member expected
+    pub compound_ids: HashMap<String, String>, // compound ID of each expected combination, when included within the expected combinations file
+    pub counted_barcode_extra_headers: Vec<Vec<String>>, // headers of the conversion file columns after the required columns, for each counted barcode
+    pub counted_barcode_extras: Vec<HashMap<String, Vec<String>>>, // values of the extra conversion file columns for each counted barcode sequence
+    pub sample_metadata_headers: Vec<String>, // headers of the sample file columns after the sample ID, such as condition or replicate
+    pub sample_metadata: HashMap<String, Vec<String>>, // values of the extra sample file columns for each sample ID
 }
 
 impl Default for BarcodeConversions {
@@ -356,81 +1699,217 @@ impl BarcodeConversions {
             sample_seqs: AHashSet::new(),
             counted_barcodes_hash: Vec::new(),
             counted_barcode_seqs: Vec::new(),
+            expected_combinations: Vec::new(),
+            compound_ids: HashMap::new(),
+            counted_barcode_extra_headers: Vec::new(),
+            counted_barcode_extras: Vec::new(),
+            sample_metadata_headers: Vec::new(),
+            sample_metadata: HashMap::new(),
         }
     }
 
-    /// Reads in comma separated barcode file (CSV).  The columns need to have headers.  The first column needs to be the nucleotide barcode
-    /// and the second needs to be the ID
-    pub fn sample_barcode_file_conversion(&mut self, barcode_path: &str) -> Result<()> {
+    /// Reads in comma or tab separated barcode file.  The columns need to have headers.  The first column needs to be the nucleotide barcode
+    /// and the second needs to be the ID, unless the columns are named Barcode and Sample_ID.  Any further columns, such as condition or replicate, are kept as metadata of the sample.
+    /// When the sample barcode length is given, each barcode is checked to be that length
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::BarcodeConversions;
+    ///
+    /// // Tab separated with a byte order mark, Windows line endings, and the named columns in another order.  Quoted values can
+    /// // hold the delimiter and doubled quotes
+    /// let sample_path = std::env::temp_dir().join("barcode_count_sample_conversion_example.tsv");
+    /// std::fs::write(
+    ///     &sample_path,
+    ///     "\u{feff}Sample_ID\tCondition\tBarcode\r\nSample_1\t\"DMSO\t0.1%\"\tAGCTAGCT\r\nSample_2\t\"\"\"Treated\"\"\"\tTTGGAACC\r\n",
+    /// )
+    /// .unwrap();
+    /// let mut barcode_conversions = BarcodeConversions::new();
+    /// barcode_conversions
+    ///     .sample_barcode_file_conversion(sample_path.to_str().unwrap(), Some(8))
+    ///     .unwrap();
+    /// assert_eq!(barcode_conversions.samples_barcode_hash["AGCTAGCT"], "Sample_1");
+    /// assert_eq!(barcode_conversions.samples_barcode_hash["TTGGAACC"], "Sample_2");
+    /// assert_eq!(barcode_conversions.sample_metadata_headers, vec!["Condition".to_string()]);
+    /// assert_eq!(barcode_conversions.sample_metadata["Sample_1"], vec!["DMSO\t0.1%".to_string()]);
+    /// assert_eq!(barcode_conversions.sample_metadata["Sample_2"], vec!["\"Treated\"".to_string()]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the file, line, and column for any line missing a barcode or ID, a barcode that is not only
+    /// A, G, C, or T, a barcode of the wrong length, or a barcode or ID that is already used
+    pub fn sample_barcode_file_conversion(
+        &mut self,
+        barcode_path: &str,
+        sample_length_option: Option<u16>,
+    ) -> Result<()> {
+        let mut sample_ids = AHashSet::new();
         // read in the sample barcode file
-        for (barcode, sample_id) in fs::read_to_string(barcode_path)
-            .context(format!("Failed to open {}", barcode_path))?
-            .lines() // split the lines
-            .skip(1) // skip the first line which should be the header
-            .map(|line| {
-                line.split(',')
-                    .take(2) // take only the first two values, or columns
-                    .map(|value| value.to_string())
-                    .collect_tuple()
-                    .unwrap_or(("".to_string(), "".to_string()))
-            })
+        let conversion_file = read_conversion_file(barcode_path, &["Barcode", "Sample_ID"])?;
+        self.sample_metadata_headers = conversion_file.extra_headers;
+        for ConversionLine {
+            line_num,
+            mut values,
+            extra_values,
+        } in conversion_file.lines
         {
-            self.samples_barcode_hash.insert(barcode, sample_id);
+            let sample_id = values.pop().unwrap();
+            let barcode = values.pop().unwrap();
+            self.sample_metadata.insert(sample_id.clone(), extra_values);
+            add_conversion(
+                &mut self.samples_barcode_hash,
+                &mut sample_ids,
+                barcode,
+                sample_id,
+                sample_length_option,
+                &format!("{} line {}", barcode_path, line_num),
+            )?;
         }
         Ok(())
     }
 
-    /// Reads in comma separated barcode file (CSV).  The columns need to have headers.  The first column needs to be the nucleotide barcode
-    /// the second needs to be the ID, and the third needs to be the barcode index location
+    /// Reads in comma or tab separated barcode file.  The columns need to have headers.  The first column needs to be the nucleotide barcode
+    /// the second needs to be the ID, and the third needs to be the barcode index location, unless the columns are named Barcode,
+    /// Barcode_ID, and Barcode_Number.  The number of counted barcodes is
+    /// the number of barcode lengths given.  Any further columns, such as SMILES or gene names, are kept for the counts files
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::BarcodeConversions;
     ///
-    /// # Panics
+    /// // Comma separated without the Barcode, Barcode_ID, and Barcode_Number names, so the first three columns are used in order
+    /// let barcode_path = std::env::temp_dir().join("barcode_count_barcode_conversion_example.csv");
+    /// std::fs::write(
+    ///     &barcode_path,
+    ///     "Sequence,Name,Cycle,SMILES\nCAGAGA,\"BB1, Boc\",1,\"CC(=O)O,N\"\nTGATTG,\"BB \"\"2\"\"\",2,C\n",
+    /// )
+    /// .unwrap();
+    /// let mut barcode_conversions = BarcodeConversions::new();
+    /// barcode_conversions
+    ///     .barcode_file_conversion(barcode_path.to_str().unwrap(), &[6, 6])
+    ///     .unwrap();
+    /// assert_eq!(barcode_conversions.counted_barcodes_hash[0]["CAGAGA"], "BB1, Boc");
+    /// assert_eq!(barcode_conversions.counted_barcodes_hash[1]["TGATTG"], "BB \"2\"");
+    /// assert_eq!(barcode_conversions.counted_barcode_extra_headers[0], vec!["SMILES".to_string()]);
+    /// assert_eq!(barcode_conversions.counted_barcode_extras[0]["CAGAGA"], vec!["CC(=O)O,N".to_string()]);
+    /// ```
+    ///
+    /// # Errors
     ///
-    /// This panics if the third column of the barcode conversion file does not contain integers.  Also
-    /// panics if not all integers for barcode numbers is within this columns
+    /// Returns an error naming the file, line, and column for any line missing one of the three values, a barcode index that is not
+    /// between 1 and the number of counted barcodes, a barcode that is not only A, G, C, or T, a barcode of the wrong
+    /// length, or a barcode or ID that is already used for the same index.  Also errors if any barcode index is missing
     pub fn barcode_file_conversion(
         &mut self,
         barcode_path: &str,
-        barcode_num: usize,
+        barcode_lengths: &[u16],
     ) -> Result<()> {
-        // read in the sample barcode file
-        let barcode_vecs = fs::read_to_string(barcode_path)
-            .context(format!("Failed to read {}", barcode_path))?
-            .lines() // split the lines
-            .skip(1) // skip the first line which should be the header
-            .map(|line| {
-                line.split(',')
-                    .take(3) // take only the first three values, or columns
-                    .map(|value| value.to_string())
-                    .collect_tuple()
-                    .unwrap_or(("".to_string(), "".to_string(), "".to_string()))
-            }) // comma split the line into a tuple with the first being the key and the last the value
-            .collect::<Vec<(String, String, String)>>();
+        let barcode_num = barcode_lengths.len();
+        let mut barcode_ids = Vec::new();
         for _ in 0..barcode_num {
             self.counted_barcodes_hash.push(HashMap::new());
+            self.counted_barcode_extras.push(HashMap::new());
+            barcode_ids.push(AHashSet::new());
         }
-        let mut barcode_num_contained = AHashSet::new();
-        for (barcode, id, barcode_num) in barcode_vecs {
-            let barcode_num_usize = barcode_num.parse::<usize>().context(format!(
-                "Third column of barcode file contains something other than an integer: {}",
-                barcode_num
-            ))? - 1;
-            barcode_num_contained.insert(barcode_num_usize);
-            self.counted_barcodes_hash[barcode_num_usize].insert(barcode, id);
-        }
-        let mut missing_barcode_num = Vec::new();
-        for x in 0..barcode_num {
-            if !barcode_num_contained.contains(&x) {
-                missing_barcode_num.push(x)
-            }
+        // read in the counted barcode file
+        let conversion_file =
+            read_conversion_file(barcode_path, &["Barcode", "Barcode_ID", "Barcode_Number"])?;
+        self.counted_barcode_extra_headers = vec![conversion_file.extra_headers; barcode_num];
+        for ConversionLine {
+            line_num,
+            mut values,
+            extra_values,
+        } in conversion_file.lines
+        {
+            let location = format!("{} line {}", barcode_path, line_num);
+            let barcode_num_str = values.pop().unwrap();
+            let id = values.pop().unwrap();
+            let barcode = values.pop().unwrap();
+            let barcode_index = match barcode_num_str.parse::<usize>() {
+                Ok(barcode_number) if (1..=barcode_num).contains(&barcode_number) => {
+                    barcode_number - 1
+                }
+                _ => bail!(
+                    "{}: barcode number '{}' in the Barcode_Number column needs to be an integer from 1 to {}",
+                    location,
+                    barcode_num_str,
+                    barcode_num
+                ),
+            };
+            self.counted_barcode_extras[barcode_index].insert(barcode.clone(), extra_values);
+            add_conversion(
+                &mut self.counted_barcodes_hash[barcode_index],
+                &mut barcode_ids[barcode_index],
+                barcode,
+                id,
+                Some(barcode_lengths[barcode_index]),
+                &location,
+            )?;
         }
+        let missing_barcode_num = self
+            .counted_barcodes_hash
+            .iter()
+            .enumerate()
+            .filter(|(_, barcode_hash)| barcode_hash.is_empty())
+            .map(|(barcode_index, _)| barcode_index + 1)
+            .collect::<Vec<usize>>();
         if !missing_barcode_num.is_empty() {
-            return Err(anyhow!(format!(
-                "Barcode conversion file missing barcode numers {:?} in the third column",
+            bail!(
+                "Barcode conversion file {} missing barcode numbers {:?} in the Barcode_Number column",
+                barcode_path,
                 missing_barcode_num
-            )));
+            );
+        }
+        Ok(())
+    }
+    /// Reads in the conversion files set for each counted barcode within a structured format file.  Each file is a comma
+    /// or tab separated file with headers, where the first column is the nucleotide barcode and the second is the ID, unless the
+    /// columns are named Barcode and Barcode_ID.  Any further
+    /// columns are kept for the counts files.  Counted barcodes without a file are not converted or error corrected.  Each file
+    /// is validated the same as the barcode conversion file
+    pub fn region_barcode_file_conversion(
+        &mut self,
+        conversion_files: &[Option<String>],
+        barcode_lengths: &[u16],
+    ) -> Result<()> {
+        for (conversion_file_option, barcode_length) in conversion_files.iter().zip(barcode_lengths)
+        {
+            let mut barcode_hash = HashMap::new();
+            let mut barcode_extras = HashMap::new();
+            let mut barcode_extra_headers = Vec::new();
+            if let Some(conversion_file) = conversion_file_option {
+                let mut barcode_ids = AHashSet::new();
+                let region_conversion =
+                    read_conversion_file(conversion_file, &["Barcode", "Barcode_ID"])?;
+                barcode_extra_headers = region_conversion.extra_headers;
+                for ConversionLine {
+                    line_num,
+                    mut values,
+                    extra_values,
+                } in region_conversion.lines
+                {
+                    let id = values.pop().unwrap();
+                    let barcode = values.pop().unwrap();
+                    barcode_extras.insert(barcode.clone(), extra_values);
+                    add_conversion(
+                        &mut barcode_hash,
+                        &mut barcode_ids,
+                        barcode,
+                        id,
+                        Some(*barcode_length),
+                        &format!("{} line {}", conversion_file, line_num),
+                    )?;
+                }
+            }
+            self.counted_barcodes_hash.push(barcode_hash);
+            self.counted_barcode_extras.push(barcode_extras);
+            self.counted_barcode_extra_headers
+                .push(barcode_extra_headers);
         }
         Ok(())
     }
+
     /// Creates a hashmap of all sample barcode sequences in order to compare for sequencing errors
     pub fn get_sample_seqs(&mut self) {
         if !self.samples_barcode_hash.is_empty() {
@@ -454,6 +1933,253 @@ impl BarcodeConversions {
                 .collect::<Vec<AHashSet<String>>>();
         }
     }
+
+    /// Reads in a comma or tab separated file of the counted barcode combinations expected within the library.  The columns need to have
+    /// headers, with one column for each counted barcode in the same order as the sequence format, unless the columns are named
+    /// with the barcode_names of the sequence format, ie Barcode_1 to Barcode_n or the names within a structured format file.  Each value can be either the nucleotide barcode or its ID from the counted barcode conversion, which
+    /// needs to be read in first.  When the header has another column, that column is the compound ID of each combination
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the file, line, and column for any line with a missing value, a value that is not a barcode or ID for its
+    /// counted barcode, or a combination that is already listed
+    pub fn expected_combination_file_conversion(
+        &mut self,
+        combination_path: &str,
+        barcode_names: &[String],
+    ) -> Result<()> {
+        let conversion_file = read_conversion_file(
+            combination_path,
+            &barcode_names
+                .iter()
+                .map(|barcode_name| barcode_name.as_str())
+                .collect::<Vec<&str>>(),
+        )?;
+        // Any column after the barcodes holds the compound ID
+        let compound_header_option = conversion_file.extra_headers.first();
+        // Allow each value to be either the barcode or the ID
+        let id_barcode_hashes = self
+            .counted_barcodes_hash
+            .iter()
+            .map(|barcode_hash| {
+                barcode_hash
+                    .iter()
+                    .map(|(barcode, id)| (id.clone(), barcode.clone()))
+                    .collect::<HashMap<String, String>>()
+            })
+            .collect::<Vec<HashMap<String, String>>>();
+        let mut combinations_found = AHashSet::new();
+        for ConversionLine {
+            line_num,
+            values,
+            extra_values,
+        } in conversion_file.lines
+        {
+            let compound_id_option = match compound_header_option {
+                Some(compound_header) => match extra_values.into_iter().next() {
+                    Some(compound_id) if !compound_id.is_empty() => Some(compound_id),
+                    _ => bail!(
+                        "{} line {}: missing a value for the '{}' column",
+                        combination_path,
+                        line_num,
+                        compound_header
+                    ),
+                },
+                None => None,
+            };
+            let mut barcodes = Vec::new();
+            for (barcode_index, value) in values.into_iter().enumerate() {
+                if self.counted_barcodes_hash[barcode_index].contains_key(&value) {
+                    barcodes.push(value);
+                } else if let Some(barcode) = id_barcode_hashes[barcode_index].get(&value) {
+                    barcodes.push(barcode.clone());
+                } else {
+                    bail!(
+                        "{} line {}: '{}' is not a barcode or ID for {}",
+                        combination_path,
+                        line_num,
+                        value,
+                        barcode_names[barcode_index]
+                    );
+                }
+            }
+            let combination = barcodes.join(",");
+            if !combinations_found.insert(combination.clone()) {
+                bail!(
+                    "{} line {}: combination '{}' is listed more than once",
+                    combination_path,
+                    line_num,
+                    combination
+                );
+            }
+            if let Some(compound_id) = compound_id_option {
+                self.compound_ids.insert(combination.clone(), compound_id);
+            }
+            self.expected_combinations.push(combination);
+        }
+        Ok(())
+    }
+
+    /// Sets the expected combinations to every barcode within the counted barcode conversion, sorted by ID.  Only possible when
+    /// there is a single counted barcode, so errors otherwise
+    pub fn single_barcode_combinations(&mut self) -> Result<()> {
+        if self.counted_barcodes_hash.len() != 1 {
+            bail!("All library members can only be listed without an expected combinations file when there is a single counted barcode")
+        }
+        self.expected_combinations = self.counted_barcodes_hash[0]
+            .iter()
+            .sorted_by(|a, b| a.1.cmp(b.1))
+            .map(|(barcode, _)| barcode.clone())
+            .collect();
+        Ok(())
+    }
+}
+
+/// A conversion file, with the values of the required columns kept apart from any extra columns
+struct ConversionFile {
+    extra_headers: Vec<String>, // headers of the columns that are not required, in file order
+    lines: Vec<ConversionLine>, // each line with values, skipping empty lines
+}
+
+/// A line of a conversion file
+struct ConversionLine {
+    line_num: usize,           // line number within the file, starting at 1
+    values: Vec<String>, // values of the required columns, in the order the columns were requested
+    extra_values: Vec<String>, // values of the extra columns, which are empty when missing
+}
+
+/// Reads a comma or tab separated conversion file with headers.  The delimiter is whichever of the two is more common within the
+/// header.  Values can be quoted to contain the delimiter, and a byte order mark or Windows line endings are removed.  Each
+/// required column is found by its header name, ignoring case, when every required column name is within the header.  Otherwise
+/// the required columns are the first columns in order.  Any other columns are returned as extra columns.  Any line with a missing
+/// required value errors with the file, line number, and column
+fn read_conversion_file(conversion_path: &str, column_names: &[&str]) -> Result<ConversionFile> {
+    let conversion_text = fs::read_to_string(conversion_path)
+        .context(format!("Failed to read {}", conversion_path))?;
+    let conversion_text = conversion_text.trim_start_matches('\u{feff}');
+    let header_line = conversion_text.lines().next().unwrap_or_default();
+    let delimiter = if header_line.matches('\t').count() > header_line.matches(',').count() {
+        b'\t'
+    } else {
+        b','
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(conversion_text.as_bytes());
+    let headers = reader
+        .headers()
+        .context(format!("Failed to read the header of {}", conversion_path))?
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<String>>();
+
+    // Find the column of each required value, by name when all of the names are within the header
+    let named_columns = column_names
+        .iter()
+        .map(|column_name| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column_name))
+        })
+        .collect::<Option<Vec<usize>>>();
+    let required_columns = named_columns.unwrap_or_else(|| (0..column_names.len()).collect());
+    let extra_columns = (0..headers.len())
+        .filter(|column| !required_columns.contains(column))
+        .collect::<Vec<usize>>();
+
+    let mut lines = Vec::new();
+    for record_result in reader.records() {
+        let record = record_result.context(format!("Failed to read {}", conversion_path))?;
+        if record.iter().all(|value| value.is_empty()) {
+            continue;
+        }
+        let line_num = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        let mut values = Vec::new();
+        for (column, column_name) in required_columns.iter().zip(column_names) {
+            match record.get(*column) {
+                Some(value) if !value.is_empty() => values.push(value.to_string()),
+                _ => bail!(
+                    "{} line {}: missing a value for the '{}' column",
+                    conversion_path,
+                    line_num,
+                    headers
+                        .get(*column)
+                        .map_or(*column_name, |header| header.as_str())
+                ),
+            }
+        }
+        let extra_values = extra_columns
+            .iter()
+            .map(|column| record.get(*column).unwrap_or_default().to_string())
+            .collect();
+        lines.push(ConversionLine {
+            line_num,
+            values,
+            extra_values,
+        });
+    }
+    Ok(ConversionFile {
+        extra_headers: extra_columns
+            .iter()
+            .map(|column| headers[*column].clone())
+            .collect(),
+        lines,
+    })
+}
+
+/// Adds a barcode and its ID to the conversion hashmap.  Errors, naming the location given, if the barcode contains anything other
+/// than A, G, C, or T, is not the expected length, or if either the barcode or ID was already added
+fn add_conversion(
+    barcode_hash: &mut HashMap<String, String>,
+    barcode_ids: &mut AHashSet<String>,
+    barcode: String,
+    id: String,
+    length_option: Option<u16>,
+    location: &str,
+) -> Result<()> {
+    if let Some(nucleotide) = barcode
+        .chars()
+        .find(|nucleotide| !"AGCT".contains(*nucleotide))
+    {
+        bail!(
+            "{}: barcode '{}' contains '{}'.  Only A, G, C, or T are allowed",
+            location,
+            barcode,
+            nucleotide
+        );
+    }
+    if let Some(length) = length_option {
+        if barcode.chars().count() != length as usize {
+            bail!(
+                "{}: barcode '{}' is {} nucleotides but the sequence format has {}",
+                location,
+                barcode,
+                barcode.chars().count(),
+                length
+            );
+        }
+    }
+    if let Some(existing_id) = barcode_hash.get(&barcode) {
+        bail!(
+            "{}: barcode '{}' is duplicated.  It is already used for ID '{}'",
+            location,
+            barcode,
+            existing_id
+        );
+    }
+    if !barcode_ids.insert(id.clone()) {
+        bail!(
+            "{}: ID '{}' is duplicated.  It is already used for another barcode",
+            location,
+            id
+        );
+    }
+    barcode_hash.insert(barcode, id);
+    Ok(())
 }
 
 /// Struct of how many sequencing errrors are allowed
@@ -613,6 +2339,121 @@ impl MaxSeqErrors {
     pub fn max_barcode_errors(&self) -> &[u16] {
         &self.barcode
     }
+
+    /// Returns the minimum average read quality score allowed per barcode
+    pub fn min_quality(&self) -> f32 {
+        self.min_quality
+    }
+
+    /// Replaces the maximum errors with any that were set for individual regions within a structured format file
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::info::{FormatRegion, MaxSeqErrors, SequenceFormat};
+    ///
+    /// let format_data = "
+    /// [[region]]
+    /// type = \"constant\"
+    /// sequence = \"AGCTAGCTAG\"
+    ///
+    /// [[region]]
+    /// type = \"barcode\"
+    /// length = 8
+    ///
+    /// [[region]]
+    /// type = \"barcode\"
+    /// length = 8
+    /// max_errors = 0
+    /// ";
+    /// let regions = FormatRegion::from_toml(format_data).unwrap();
+    /// let sequence_format = SequenceFormat::from_regions(&regions).unwrap();
+    /// let mut max_sequence_errors = MaxSeqErrors::new(None, None, None, vec![8, 8], None, 10, 0.0);
+    /// max_sequence_errors.set_region_errors(&sequence_format);
+    /// assert_eq!(max_sequence_errors.max_barcode_errors(), vec![1, 0]);
+    /// ```
+    pub fn set_region_errors(&mut self, sequence_format: &SequenceFormat) {
+        if let Some(sample_errors) = sequence_format.sample_max_errors {
+            self.sample_barcode = sample_errors;
+        }
+        for (max_errors, region_errors_option) in self
+            .barcode
+            .iter_mut()
+            .zip(sequence_format.barcode_max_errors.iter())
+        {
+            if let Some(region_errors) = region_errors_option {
+                *max_errors = *region_errors;
+            }
+        }
+        if let Some(constant_errors) = sequence_format.constant_max_errors {
+            self.constant_region = constant_errors;
+        }
+    }
+
+    /// Finds the barcode sets where the closest two barcodes are too similar for the number of errors allowed.  With a minimum
+    /// Hamming distance of d between barcodes, only (d - 1) / 2 errors can be allowed before a read with errors can be within
+    /// the allowed errors of two barcodes.  Barcode sets that are empty, and therefore not error corrected, are skipped.  Since
+    /// every pair of barcodes is compared, sets with more than 10,000 barcodes are also skipped unless check_large_sets is set
+    ///
+    /// # Example
+    /// ```
+    /// use ahash::AHashSet;
+    /// use barcode_count::info::MaxSeqErrors;
+    ///
+    /// let max_sequence_errors = MaxSeqErrors::new(None, None, Some(1), vec![6], None, 10, 0.0);
+    /// let sample_seqs = AHashSet::new();
+    /// let counted_barcode_seqs = vec![["AGCTAG", "AGCTTC", "TTTTTT"]
+    ///     .iter()
+    ///     .map(|barcode| barcode.to_string())
+    ///     .collect::<AHashSet<String>>()];
+    /// let collisions = max_sequence_errors.barcode_collisions(&sample_seqs, &counted_barcode_seqs, false);
+    /// assert_eq!(collisions.len(), 1);
+    /// assert_eq!(collisions[0].distance, 2);
+    /// assert_eq!(collisions[0].safe_max_errors(), 0);
+    /// ```
+    pub fn barcode_collisions(
+        &self,
+        sample_seqs: &AHashSet<String>,
+        counted_barcode_seqs: &[AHashSet<String>],
+        check_large_sets: bool,
+    ) -> Vec<BarcodeCollision> {
+        let mut barcode_sets = vec![(
+            "Sample barcode".to_string(),
+            sample_seqs,
+            self.sample_barcode,
+        )];
+        for (barcode_index, (barcode_seqs, max_errors)) in
+            counted_barcode_seqs.iter().zip(&self.barcode).enumerate()
+        {
+            let region = if counted_barcode_seqs.len() > 1 {
+                format!("Counted barcode {}", barcode_index + 1)
+            } else {
+                "Counted barcode".to_string()
+            };
+            barcode_sets.push((region, barcode_seqs, *max_errors));
+        }
+
+        let mut collisions = Vec::new();
+        for (region, barcode_seqs, max_errors) in barcode_sets {
+            // Any two different barcodes are safe when no errors are allowed
+            if max_errors == 0
+                || (!check_large_sets && barcode_seqs.len() > MAX_COLLISION_CHECK_BARCODES)
+            {
+                continue;
+            }
+            if let Some((distance, barcodes)) = closest_barcodes(barcode_seqs) {
+                let collision = BarcodeCollision {
+                    region,
+                    barcodes,
+                    distance,
+                    max_errors,
+                };
+                if max_errors > collision.safe_max_errors() {
+                    collisions.push(collision);
+                }
+            }
+        }
+        collisions
+    }
 }
 
 impl fmt::Display for MaxSeqErrors {
@@ -658,9 +2499,132 @@ impl fmt::Display for MaxSeqErrors {
     }
 }
 
+/// The most barcodes within a set that are checked for collisions by default.  Larger sets are only checked with fail_on_collision,
+/// since comparing every pair delays counting
+const MAX_COLLISION_CHECK_BARCODES: usize = 10_000;
+
+/// The closest two barcodes within a barcode set when they are too similar for the number of errors allowed
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarcodeCollision {
+    pub region: String, // Name of the barcode set, ie 'Sample barcode' or 'Counted barcode 1'
+    pub barcodes: (String, String), // The closest two barcodes within the set
+    pub distance: u16,  // Hamming distance between the closest two barcodes
+    pub max_errors: u16, // Maximum errors allowed within the region
+}
+
+impl BarcodeCollision {
+    /// Returns the most errors that can be allowed without a read being within the allowed errors of two barcodes
+    pub fn safe_max_errors(&self) -> u16 {
+        self.distance.saturating_sub(1) / 2
+    }
+}
+
+impl fmt::Display for BarcodeCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} and {} differ by {} nucleotides but {} errors are allowed.  Reads can be within the allowed errors of both barcodes.  Set the maximum errors to {} or less",
+            self.region,
+            self.barcodes.0,
+            self.barcodes.1,
+            self.distance,
+            self.max_errors,
+            self.safe_max_errors()
+        )
+    }
+}
+
+/// Finds the closest two barcodes within the set by Hamming distance.  Returns None when there are fewer than two barcodes
+fn closest_barcodes(barcode_seqs: &AHashSet<String>) -> Option<(u16, (String, String))> {
+    // Sort so that ties return the same barcodes each run
+    let mut barcodes = barcode_seqs
+        .iter()
+        .map(|barcode| barcode.as_bytes())
+        .collect::<Vec<&[u8]>>();
+    barcodes.sort_unstable();
+    // Different barcodes are at least one apart, so stop comparing once a pair at a distance of one is found.  Only barcodes after the
+    // first one with such a pair are skipped so that the same pair is returned each run
+    let first_closest_index = AtomicUsize::new(usize::MAX);
+    let (distance, first_index, second_index) = (0..barcodes.len())
+        .into_par_iter()
+        .filter_map(|first_index| {
+            if first_index > first_closest_index.load(Ordering::Relaxed) {
+                return None;
+            }
+            let mut closest: Option<(u16, usize, usize)> = None;
+            for second_index in first_index + 1..barcodes.len() {
+                let distance = hamming_distance(barcodes[first_index], barcodes[second_index]);
+                if closest.is_none_or(|(closest_distance, _, _)| distance < closest_distance) {
+                    closest = Some((distance, first_index, second_index));
+                    if distance <= 1 {
+                        first_closest_index.fetch_min(first_index, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
+            closest
+        })
+        .min()?;
+    Some((
+        distance,
+        (
+            String::from_utf8_lossy(barcodes[first_index]).to_string(),
+            String::from_utf8_lossy(barcodes[second_index]).to_string(),
+        ),
+    ))
+}
+
+/// Finds the known barcodes closest to the sequence by Hamming distance.  Returns the distance with every known barcode at
+/// that distance, sorted, or None when there are no known barcodes
+///
+/// # Example
+/// ```
+/// use ahash::AHashSet;
+/// use barcode_count::info::nearest_barcodes;
+///
+/// let known_barcodes = ["AAAA", "AAGG", "CCCC"]
+///     .iter()
+///     .map(|barcode| barcode.to_string())
+///     .collect::<AHashSet<String>>();
+/// assert_eq!(nearest_barcodes("AAAT", &known_barcodes), Some((1, vec!["AAAA".to_string()])));
+/// assert_eq!(nearest_barcodes("AAGA", &known_barcodes), Some((1, vec!["AAAA".to_string(), "AAGG".to_string()])));
+/// ```
+pub fn nearest_barcodes(
+    sequence: &str,
+    known_barcodes: &AHashSet<String>,
+) -> Option<(u16, Vec<String>)> {
+    let distances = known_barcodes
+        .iter()
+        .map(|barcode| {
+            (
+                hamming_distance(sequence.as_bytes(), barcode.as_bytes()),
+                barcode,
+            )
+        })
+        .collect::<Vec<(u16, &String)>>();
+    let min_distance = distances.iter().map(|(distance, _)| *distance).min()?;
+    let nearest = distances
+        .into_iter()
+        .filter(|(distance, _)| *distance == min_distance)
+        .map(|(_, barcode)| barcode.clone())
+        .sorted()
+        .collect();
+    Some((min_distance, nearest))
+}
+
+/// Counts the mismatched nucleotides between two barcodes.  Any difference in length is counted as mismatches
+fn hamming_distance(first_barcode: &[u8], second_barcode: &[u8]) -> u16 {
+    let mismatches = first_barcode
+        .iter()
+        .zip(second_barcode)
+        .filter(|(first, second)| first != second)
+        .count();
+    (mismatches + first_barcode.len().abs_diff(second_barcode.len())) as u16
+}
+
 #[derive(Debug)]
 pub enum ResultsHashmap {
-    RandomBarcode(HashMap<String, HashMap<String, AHashSet<String>>>),
+    RandomBarcode(HashMap<String, HashMap<String, (AHashSet<String>, usize)>>), // The random barcodes and the reads, including duplicates, of each barcode combination
     NoRandomBarcode(HashMap<String, HashMap<String, usize>>),
 }
 
@@ -669,7 +2633,7 @@ pub enum ResultsHashmap {
 pub struct Results {
     pub results_hashmap: ResultsHashmap, // holds the counted results
     empty_count_hash: HashMap<String, usize>, // An empty hashmap that is used a few times and therefor stored within the struct
-    empty_random_hash: HashMap<String, AHashSet<String>>,
+    empty_random_hash: HashMap<String, (AHashSet<String>, usize)>,
     sample_conversion_omited: bool,
 }
 
@@ -692,7 +2656,7 @@ impl Results {
         // If sample name conversion was included, add all sample names to the hashmaps used to count
         let mut sample_conversion_omited = false;
         // create empty hashmaps to insert and have the sample name included.  This is so sample name doesn't need to be searched each time
-        let empty_random_hash: HashMap<String, AHashSet<String>> = HashMap::new();
+        let empty_random_hash: HashMap<String, (AHashSet<String>, usize)> = HashMap::new();
         let empty_count_hash: HashMap<String, usize> = HashMap::new();
         // If there is a sample barcode file included, add these as keys in the relevant count hashmap
         if !samples_barcode_hash.is_empty() {
@@ -731,6 +2695,71 @@ impl Results {
         }
     }
 
+    /// Returns the number of counted reads for each sample barcode.  With a random barcode, duplicates are not included
+    ///
+    /// # Example
+    /// ```
+    /// use ahash::HashMap;
+    /// use barcode_count::info::Results;
+    ///
+    /// let mut results = Results::new(&HashMap::default(), false, true);
+    /// results.add_count("AGCT", None, "TTTT".to_string());
+    /// results.add_count("AGCT", None, "GGGG".to_string());
+    /// results.add_count("CCCC", None, "TTTT".to_string());
+    /// let sample_read_counts = results.sample_read_counts();
+    /// assert_eq!(sample_read_counts.get("AGCT"), Some(&2));
+    /// assert_eq!(sample_read_counts.get("CCCC"), Some(&1));
+    /// ```
+    pub fn sample_read_counts(&self) -> HashMap<String, usize> {
+        match &self.results_hashmap {
+            ResultsHashmap::NoRandomBarcode(count_hashmap) => count_hashmap
+                .iter()
+                .map(|(sample_barcode, counts)| (sample_barcode.clone(), counts.values().sum()))
+                .collect(),
+            ResultsHashmap::RandomBarcode(random_hashmap) => random_hashmap
+                .iter()
+                .map(|(sample_barcode, random_barcodes)| {
+                    (
+                        sample_barcode.clone(),
+                        random_barcodes
+                            .values()
+                            .map(|(randoms, _)| randoms.len())
+                            .sum(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the number of reads counted for the barcode combination within the sample, or 0 if it was not counted.  With a
+    /// random barcode, duplicates are included
+    ///
+    /// # Example
+    /// ```
+    /// use ahash::HashMap;
+    /// use barcode_count::info::Results;
+    ///
+    /// let mut results = Results::new(&HashMap::default(), true, true);
+    /// let random_barcode = "AAAA".to_string();
+    /// results.add_count("AGCT", Some(&random_barcode), "TTTT".to_string());
+    /// results.add_count("AGCT", Some(&random_barcode), "TTTT".to_string());
+    /// assert_eq!(results.read_count("AGCT", "TTTT"), 2);
+    /// assert_eq!(results.read_count("AGCT", "GGGG"), 0);
+    /// ```
+    pub fn read_count(&self, sample_barcode: &str, code: &str) -> usize {
+        match &self.results_hashmap {
+            ResultsHashmap::NoRandomBarcode(count_hashmap) => count_hashmap
+                .get(sample_barcode)
+                .and_then(|sample_counts| sample_counts.get(code))
+                .copied(),
+            ResultsHashmap::RandomBarcode(random_hashmap) => random_hashmap
+                .get(sample_barcode)
+                .and_then(|sample_counts| sample_counts.get(code))
+                .map(|(_, reads)| *reads),
+        }
+        .unwrap_or(0)
+    }
+
     /// Adds the count to results hashmap
     pub fn add_count(
         &mut self,
@@ -778,14 +2807,17 @@ impl Results {
                     // If the barcodes_hashmap is not empty
                     // but doesn't contain the barcode
                     if let std::collections::hash_map::Entry::Vacant(e) = barcodes_hashmap.entry(barcode_string.clone()) {
-                        // insert the hashmap<barcode_id, Set<random_barcodes>>
+                        // insert the hashmap<barcode_id, (Set<random_barcodes>, reads)>
                         let mut intermediate_set = AHashSet::new();
                         intermediate_set
                             .insert(random_barcode.unwrap_or(&"".to_string()).to_string());
-                        e.insert(intermediate_set);
+                        e.insert((intermediate_set, 1));
                     } else {
-                        // if the hashmap<sample_id, hashmap<barcode_id, Set<>> exists, check to see if the random barcode already was inserted
-                        let random_set = barcodes_hashmap.get_mut(&barcode_string).unwrap();
+                        // if the hashmap<sample_id, hashmap<barcode_id, Set<>> exists, check to see if the random barcode already was inserted.
+                        // The read is counted either way so that duplicates are included within the reads
+                        let (random_set, reads) =
+                            barcodes_hashmap.get_mut(&barcode_string).unwrap();
+                        *reads += 1;
                         return random_set
                             .insert(random_barcode.unwrap_or(&"".to_string()).to_string());
                     }
@@ -794,8 +2826,8 @@ impl Results {
                     let mut intermediate_set = AHashSet::new();
                     intermediate_set.insert(random_barcode.unwrap_or(&"".to_string()).to_string());
                     let mut intermediate_hash = HashMap::new();
-                    // create the HashMap<barcode_id, Set<RandomBarcodes>>
-                    intermediate_hash.insert(barcode_string.to_string(), intermediate_set);
+                    // create the HashMap<barcode_id, (Set<RandomBarcodes>, reads)>
+                    intermediate_hash.insert(barcode_string.to_string(), (intermediate_set, 1));
                     // insert this into the random_hashmap connected to the sample_ID
                     random_hashmap.insert(sample_barcode.to_string(), intermediate_hash);
                 }
@@ -808,6 +2840,32 @@ impl Results {
     }
 }
 
+/// Splits comma separated columns.  Quoted columns, which can contain commas, are kept whole along with their quotes
+///
+/// # Example
+/// ```
+/// use barcode_count::info::split_columns;
+///
+/// assert_eq!(split_columns("BB1,\"BB2,a\","), vec!["BB1", "\"BB2,a\"", ""]);
+/// ```
+pub fn split_columns(row: &str) -> Vec<&str> {
+    let mut columns = Vec::new();
+    let mut column_start = 0;
+    let mut quoted = false;
+    for (index, character) in row.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                columns.push(&row[column_start..index]);
+                column_start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    columns.push(&row[column_start..]);
+    columns
+}
+
 /// A struct which holds hte enriched single and double counted barcodes.  Useful for DEL.  This struct is used during output.
 pub struct ResultsEnrichment {
     pub single_hashmap: HashMap<String, HashMap<String, usize>>, // enrichment of single barcodes hash used at output
@@ -839,9 +2897,9 @@ impl ResultsEnrichment {
     /// Adds the count the the single barcode enrichment hashmap
     pub fn add_single(&mut self, sample_id: &str, barcode_string: &str, count: usize) {
         // get the number of barcodes to know homu much to iterate
-        let barcode_num = barcode_string.split(',').count();
+        let barcode_num = split_columns(barcode_string).len();
         // For each single barcode in the comma separate barcodes, create a new string with just one barcode and empty other columns
-        for (index, single_barcode) in barcode_string.split(',').enumerate() {
+        for (index, single_barcode) in split_columns(barcode_string).into_iter().enumerate() {
             let mut single_barcode_string = String::new();
             // Recreate the new comma separated barcode with only one barcode
             for x in 0..barcode_num {
@@ -867,10 +2925,10 @@ impl ResultsEnrichment {
 
     /// Adds the count to the double barcode enrichment hashmap
     pub fn add_double(&mut self, sample_id: &str, barcode_string: &str, count: usize) {
-        // get the number of barcodes to know homu much to iterate
-        let barcode_num = barcode_string.split(',').count();
         // split the barcodes into a vec from their comma separated form
-        let barcode_split = barcode_string.split(',').collect::<Vec<&str>>();
+        let barcode_split = split_columns(barcode_string);
+        // get the number of barcodes to know homu much to iterate
+        let barcode_num = barcode_split.len();
         // iterate through the number of barcode_num - 1, and take this index for the first barcode
         for first_barcode_index in 0..(barcode_num - 1) {
             // Get the amount needed to add to the first index in order to get the second index.  This is iterated to account for the second being the next barcode or two away etc. Eg from 1,2,3 = 1,2,, and 1,,3
@@ -51,7 +51,7 @@ pub fn read_fastq(
                 fastq_line_reader.post()?;
             }
             // Add to read count to print numnber of sequences read by this thread
-            if fastq_line_reader.total_reads % 10000 == 0 {
+            if fastq_line_reader.total_reads.is_multiple_of(10000) {
                 write!(lock, "{}", fastq_line_reader)?;
                 stdout.flush()?;
             }
@@ -75,7 +75,7 @@ pub fn read_fastq(
                 fastq_line_reader.post()?;
             }
             // Add to read count to print numnber of sequences read by this thread
-            if fastq_line_reader.total_reads % 10000 == 0 {
+            if fastq_line_reader.total_reads.is_multiple_of(10000) {
                 write!(lock, "{}", fastq_line_reader)?;
                 stdout.flush()?;
             }
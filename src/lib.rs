@@ -3,3 +3,4 @@ pub mod info;
 pub mod input;
 pub mod output;
 pub mod parse;
+pub mod report;
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chrono::Local;
 use std::{
     collections::VecDeque,
@@ -15,55 +15,154 @@ fn main() -> Result<()> {
     // get the argument inputs
     let mut args = barcode_count::arguments::Args::new()?;
 
-    let sequence_format = barcode_count::info::SequenceFormat::parse_format_file(&args.format)?;
-    println!("{}\n", sequence_format);
+    // Parse each sequence format file.  More than one is used when libraries with different formats are pooled
+    let mut sequence_formats = Vec::new();
+    for format_path in &args.formats {
+        let sequence_format = barcode_count::info::SequenceFormat::parse_format_file(format_path)?;
+        println!("{}\n", sequence_format);
+        // Check how many barcodes occur if either single or double barcode enrichment is callsed.  If there are too few, the argument flag is ignored for this format
+        if args.enrich && sequence_format.barcode_num < 2 {
+            eprintln!("Fewer than 2 counted barcodes.  Too few for barcode enrichment.  Argument flag is ignored");
+        }
+        sequence_formats.push(sequence_format);
+    }
 
-    // Check how many barcodes occur if either single or double barcode enrichment is callsed.  If there are too few, ignore the argument flag
-    if args.enrich && sequence_format.barcode_num < 2 {
-        eprintln!("Fewer than 2 counted barcodes.  Too few for barcode enrichment.  Argument flag is ignored");
-        args.enrich = false;
+    // If no sample barcode file was given, use one from a structured format file if it exists
+    if args.sample_barcodes_option.is_none() {
+        args.sample_barcodes_option = sequence_formats
+            .iter()
+            .find_map(|sequence_format| sequence_format.sample_conversion_option.clone());
     }
 
     // Start getting the barcode conversion with the BarcodeConversions struct
     let mut barcode_conversions = barcode_count::info::BarcodeConversions::new();
     // Create a hashmap of the sample barcodes in order to convert sequence to sample ID
     if let Some(ref samples) = args.sample_barcodes_option {
-        barcode_conversions.sample_barcode_file_conversion(samples)?;
+        // Only check the sample barcode lengths when every format has the same sample barcode length
+        let mut sample_length_option = sequence_formats[0].sample_length_option;
+        if sequence_formats
+            .iter()
+            .any(|sequence_format| sequence_format.sample_length_option != sample_length_option)
+        {
+            sample_length_option = None;
+        }
+        barcode_conversions.sample_barcode_file_conversion(samples, sample_length_option)?;
         barcode_conversions.get_sample_seqs();
     }
 
-    // Create a results struct that will contain the counts.  This is passed between threads
-    let results = Arc::new(Mutex::new(barcode_count::info::Results::new(
-        &barcode_conversions.samples_barcode_hash,
-        sequence_format.random_barcode,
-        sequence_format.sample_barcode,
-    )));
-
-    // Create a hashmap of the building block barcodes in order to convert sequence to building block
-    if let Some(ref barcodes) = args.counted_barcodes_option {
-        barcode_conversions.barcode_file_conversion(barcodes, sequence_format.barcode_num)?;
-        barcode_conversions.get_barcode_seqs();
-    }
+    // Create the results, barcode conversions, and error tracking for each sequence format
+    let mut results_vec = Vec::new();
+    let mut counted_conversions = Vec::new();
+    let mut format_matchers: Vec<barcode_count::parse::FormatMatcher> = Vec::new();
+    for (format_index, sequence_format) in sequence_formats.iter().enumerate() {
+        // Create a results struct that will contain the counts.  This is passed between threads
+        results_vec.push(Arc::new(Mutex::new(barcode_count::info::Results::new(
+            &barcode_conversions.samples_barcode_hash,
+            sequence_format.random_barcode,
+            sequence_format.sample_barcode,
+        ))));
 
-    // Create a sequencing errors Struct to track errors.  This is passed between threads
-    let sequence_errors = barcode_count::info::SequenceErrors::new();
+        // Create a hashmap of the building block barcodes in order to convert sequence to building block
+        let mut counted_conversion = barcode_count::info::BarcodeConversions::new();
+        if let Some(barcodes) = args.counted_barcodes_files.get(format_index) {
+            counted_conversion
+                .barcode_file_conversion(barcodes, &sequence_format.barcode_lengths)?;
+            counted_conversion.get_barcode_seqs();
+        } else if sequence_format
+            .barcode_conversions
+            .iter()
+            .any(|conversion| conversion.is_some())
+        {
+            // Otherwise use the conversion files set for each counted barcode within a structured format file
+            counted_conversion
+                .region_barcode_file_conversion(
+                    &sequence_format.barcode_conversions,
+                    &sequence_format.barcode_lengths,
+                )?;
+            counted_conversion.get_barcode_seqs();
+        }
+
+        // Read in the library members to write with zero counts
+        if let Some(combinations) = args.expected_combinations_files.get(format_index) {
+            if counted_conversion.counted_barcodes_hash.is_empty() {
+                bail!("Expected combinations need a counted barcode conversion file")
+            }
+            counted_conversion.expected_combination_file_conversion(
+                combinations,
+                &sequence_format.barcode_names,
+            )?;
+        } else if args.include_zero {
+            if counted_conversion.counted_barcodes_hash.is_empty() {
+                bail!("--include-zero needs a counted barcode conversion file to list the library members")
+            }
+            counted_conversion.single_barcode_combinations()?;
+        }
+
+        // Create a MaxSeqErrors struct which holds how many sequencing errors are allowed for each sequencing region
+        let mut max_errors = barcode_count::info::MaxSeqErrors::new(
+            args.sample_errors_option,
+            sequence_format.sample_length_option,
+            args.barcodes_errors_option,
+            sequence_format.barcode_lengths.clone(),
+            args.constant_errors_option,
+            sequence_format.constant_region_length,
+            args.min_average_quality_score,
+        );
+        // Errors set for individual regions within a structured format file take precedence
+        max_errors.set_region_errors(sequence_format);
+        // Display region sizes and errors allowed
+        println!("{}\n", max_errors);
+
+        // Create a sequencing errors Struct to track errors, including the matches of each region.  This is passed between threads.
+        // Reads that match none of the formats are counted as constant region errors, so this count is shared between all formats
+        let mut sequence_errors = match format_matchers.first() {
+            Some(first_format) => first_format
+                .sequence_errors
+                .share_constant_region(sequence_format, &max_errors),
+            None => {
+                barcode_count::info::SequenceErrors::new_with_regions(sequence_format, &max_errors)
+            }
+        };
+
+        // Count the reads of each known sample barcode without locking
+        sequence_errors.set_sample_barcodes(
+            &barcode_conversions.samples_barcode_hash,
+            sequence_format.sample_barcode,
+        );
+
+        // Check that the barcodes within each set are far enough apart for the errors allowed.  Large sets are only checked
+        // when the run would stop on a collision
+        let collisions = max_errors.barcode_collisions(
+            &barcode_conversions.sample_seqs,
+            &counted_conversion.counted_barcode_seqs,
+            args.fail_on_collision,
+        );
+        for collision in &collisions {
+            eprintln!("Warning: {}", collision);
+        }
+        if args.fail_on_collision && !collisions.is_empty() {
+            bail!("Barcodes are too similar for the maximum errors allowed.  Lower the maximum errors or remove --fail-on-collision");
+        }
+
+        format_matchers.push(barcode_count::parse::FormatMatcher::new(
+            sequence_format.clone(),
+            max_errors,
+            counted_conversion.counted_barcode_seqs.clone(),
+            Arc::new(
+                counted_conversion
+                    .expected_combinations
+                    .iter()
+                    .cloned()
+                    .collect(),
+            ),
+            sequence_errors,
+        ));
+        counted_conversions.push(counted_conversion);
+    }
 
     // Create a passed exit passed variable to stop reading when a thread has panicked
     let exit = Arc::new(AtomicBool::new(false));
 
-    // Create a MaxSeqErrors struct which holds how many sequencing errors are allowed for each sequencing region
-    let max_errors = barcode_count::info::MaxSeqErrors::new(
-        args.sample_errors_option,
-        sequence_format.sample_length_option,
-        args.barcodes_errors_option,
-        sequence_format.barcode_lengths.clone(),
-        args.constant_errors_option,
-        sequence_format.constant_region_length,
-        args.min_average_quality_score,
-    );
-    // Display region sizes and errors allowed
-    println!("{}\n", max_errors);
-
     let total_reads_arc = Arc::new(AtomicU32::new(0));
     // Start the multithreading scope
     rayon::scope(|s| {
@@ -87,30 +186,29 @@ fn main() -> Result<()> {
             finished_clone.store(true, Ordering::Relaxed);
         });
 
-        let shared_mut =
-            barcode_count::parse::SharedMutData::new(seq, finished, Arc::clone(&results));
+        let shared_mut = barcode_count::parse::SharedMutData::new(
+            seq,
+            finished,
+            results_vec.iter().map(Arc::clone).collect(),
+        );
         // Create processing threads.  One less than the total threads because of the single reading thread
         for _ in 1..args.threads {
             // Clone all variables needed to pass into each thread
             let shared_mut_clone = shared_mut.arc_clone();
-            let sequence_errors_clone = sequence_errors.arc_clone();
-            let sequence_format_clone = sequence_format.clone();
+            let format_matchers_clone = format_matchers.clone();
             let exit_clone = &exit;
-            let max_errors_clone = max_errors.clone();
             let sample_seqs_clone = barcode_conversions.sample_seqs.clone();
-            let counted_barcode_seqs_clone = barcode_conversions.counted_barcode_seqs.clone();
             let min_quality_score = args.min_average_quality_score;
+            let strand = args.strand;
 
             // Create a processing thread
             s.spawn(move |_| {
                 let mut parser = barcode_count::parse::SequenceParser::new(
                     shared_mut_clone,
-                    sequence_errors_clone,
-                    sequence_format_clone,
-                    max_errors_clone,
+                    format_matchers_clone,
                     sample_seqs_clone,
-                    counted_barcode_seqs_clone,
                     min_quality_score,
+                    strand,
                 );
                 parser.parse().unwrap_or_else(|err| {
                     exit_clone.store(true, Ordering::Relaxed);
@@ -121,7 +219,12 @@ fn main() -> Result<()> {
     });
 
     // Print sequencing error counts to stdout
-    println!("{}\n", sequence_errors);
+    for (format_matcher, format_path) in format_matchers.iter().zip(args.formats.iter()) {
+        if args.formats.len() > 1 {
+            println!("-{}-", format_path);
+        }
+        println!("{}\n", format_matcher.sequence_errors);
+    }
 
     // Get the end time and print compute time for the algorithm
     let elapsed_time = Local::now() - start_time;
@@ -135,23 +238,53 @@ fn main() -> Result<()> {
     println!();
 
     println!("-WRITING COUNTS-");
-    let mut output = barcode_count::output::WriteFiles::new(
-        results,
-        sequence_format.clone(),
-        barcode_conversions.counted_barcodes_hash,
-        barcode_conversions.samples_barcode_hash,
-        args,
-    )
-    .unwrap_or_else(|err| panic!("Output error: {}", err));
-    output.write_counts_files()?;
-    // Get the end time and print total time for the algorithm
-    output.write_stats_file(
-        start_time,
-        max_errors,
-        sequence_errors,
-        total_reads_arc,
-        sequence_format,
-    )?;
+    for (format_index, ((results, counted_conversion), format_matcher)) in results_vec
+        .into_iter()
+        .zip(counted_conversions)
+        .zip(format_matchers)
+        .enumerate()
+    {
+        let mut format_args = args.clone();
+        format_args.enrich = args.enrich && format_matcher.sequence_format.barcode_num > 1;
+        let mut output = barcode_count::output::WriteFiles::new(
+            results,
+            format_matcher.sequence_format.clone(),
+            counted_conversion,
+            &barcode_conversions,
+            format_args,
+            format_index,
+        )
+        .unwrap_or_else(|err| panic!("Output error: {}", err));
+        output.write_counts_files()?;
+        if args.arrow {
+            output.write_arrow_files()?;
+        }
+        if args.long {
+            output.write_long_file()?;
+        }
+        if args.mtx {
+            output.write_mtx_files()?;
+        }
+        output.write_constant_mismatch_file(&format_matcher.sequence_errors)?;
+        output.write_undetermined_sample_file(&format_matcher.sequence_errors)?;
+        output.write_unknown_barcode_file(&format_matcher.sequence_errors)?;
+        if args.multiqc {
+            output.write_multiqc_files(&format_matcher.sequence_errors)?;
+        }
+        output.write_json_report(
+            start_time,
+            &format_matcher.max_errors,
+            &format_matcher.sequence_errors,
+            &total_reads_arc,
+        )?;
+        output.write_stats_file(
+            start_time,
+            format_matcher.max_errors,
+            format_matcher.sequence_errors,
+            Arc::clone(&total_reads_arc),
+            format_matcher.sequence_format,
+        )?;
+    }
     // Get the end time and print total time for the algorithm
     let elapsed_time = Local::now() - start_time;
     println!();
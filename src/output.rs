@@ -2,8 +2,11 @@ use anyhow::{anyhow, Result};
 use chrono::{DateTime, Local};
 use num_format::{Locale, ToFormattedString};
 use std::{
-    fs::{File, OpenOptions},
-    io::{stdout, Write},
+    borrow::Cow,
+    cmp,
+    collections::BTreeMap,
+    fs::{create_dir_all, File, OpenOptions},
+    io::{stdout, BufWriter, Write},
     path::Path,
     sync::{
         atomic::{AtomicU32, Ordering},
@@ -11,14 +14,28 @@ use std::{
     },
 };
 
-use ahash::{AHashSet, HashMap, HashMapExt};
+use ahash::{AHashSet, HashMap};
+use arrow_array::{
+    builder::{StringBuilder, UInt64Builder},
+    ArrayRef, RecordBatch,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
 
+use flate2::write::GzEncoder;
 use itertools::Itertools;
 
 use crate::{
     arguments::Args,
     info::{
-        MaxSeqErrors, Results, ResultsEnrichment, ResultsHashmap, SequenceErrors, SequenceFormat,
+        nearest_barcodes, BarcodeConversions, Compression, MaxSeqErrors, Results,
+        ResultsEnrichment, ResultsHashmap, SampleReadCounts, SequenceErrors, SequenceFormat,
+        SortOrder,
+    },
+    report::{
+        FormatReport, InputReport, MaxErrorsReport, MismatchProfileReport, OutputFileReport,
+        PositionReport, ReadsReport, RegionReport, RunReport, SampleReport, SettingsReport,
+        TimingReport, ToolReport, REPORT_SCHEMA_VERSION,
     },
 };
 
@@ -35,73 +52,70 @@ pub struct WriteFiles {
     results_enriched: ResultsEnrichment,
     sequence_format: SequenceFormat,
     counted_barcodes_hash: Vec<HashMap<String, String>>,
+    expected_combinations: Vec<String>, // library members written with zero counts when include_zero is set
+    compound_ids: HashMap<String, String>, // compound ID of each counted barcode combination.  Adds a Compound_ID column when not empty
+    counted_barcode_extra_headers: Vec<Vec<String>>, // headers of the extra conversion file columns written after each counted barcode
+    counted_barcode_extras: Vec<HashMap<String, Vec<String>>>, // extra conversion file values of each counted barcode sequence
     samples_barcode_hash: HashMap<String, String>,
-    compounds_written: AHashSet<String>,
+    sample_metadata_headers: Vec<String>, // headers of the extra sample file columns.  Written after the sample within the long file
+    sample_metadata: HashMap<String, Vec<String>>, // values of the extra sample file columns for each sample ID
     args: Args,
+    format_index: usize, // index of the sequence format these results are for
+    prefix: String,      // output file prefix for this sequence format
     output_files: Vec<String>,
     output_counts: Vec<usize>,
-    merged_count: usize,
-    merge_text: String,
-    sample_text: String,
 }
 
 impl WriteFiles {
     pub fn new(
         results_arc: Arc<Mutex<Results>>,
         sequence_format: SequenceFormat,
-        counted_barcodes_hash: Vec<HashMap<String, String>>,
-        samples_barcode_hash: HashMap<String, String>,
+        counted_conversion: BarcodeConversions, // counted barcode conversion for this sequence format, along with the expected library
+        sample_conversion: &BarcodeConversions, // sample barcode conversion, along with the sample metadata
         args: Args,
+        format_index: usize,
     ) -> Result<Self> {
         let results = Arc::try_unwrap(results_arc).unwrap().into_inner().unwrap();
+        let prefix = args.format_prefix(format_index);
         Ok(WriteFiles {
             results,
             results_enriched: ResultsEnrichment::new(),
             sequence_format,
-            counted_barcodes_hash,
-            samples_barcode_hash,
-            compounds_written: AHashSet::new(),
+            counted_barcodes_hash: counted_conversion.counted_barcodes_hash,
+            expected_combinations: counted_conversion.expected_combinations,
+            compound_ids: counted_conversion.compound_ids,
+            counted_barcode_extra_headers: counted_conversion.counted_barcode_extra_headers,
+            counted_barcode_extras: counted_conversion.counted_barcode_extras,
+            samples_barcode_hash: sample_conversion.samples_barcode_hash.clone(),
+            sample_metadata_headers: sample_conversion.sample_metadata_headers.clone(),
+            sample_metadata: sample_conversion.sample_metadata.clone(),
             args,
+            format_index,
+            prefix,
             output_files: Vec::new(),
             output_counts: Vec::new(),
-            merged_count: 0,
-            merge_text: String::new(),
-            sample_text: String::new(),
         })
     }
 
     /// Sets up and writes the results file.  Works for either with or without a random barcode
     pub fn write_counts_files(&mut self) -> Result<()> {
-        let unknown_sample = "barcode".to_string();
-        // Pull all sample IDs from either random hashmap or counts hashmap
-        let mut sample_barcodes = match &self.results.results_hashmap {
-            ResultsHashmap::RandomBarcode(random_hashmap) => {
-                random_hashmap.keys().cloned().collect::<Vec<String>>()
-            }
-            ResultsHashmap::NoRandomBarcode(count_hashmap) => {
-                count_hashmap.keys().cloned().collect::<Vec<String>>()
-            }
-        };
+        let sample_barcodes = self.sorted_sample_barcodes();
 
         if self.args.enrich {
             self.results_enriched.add_sample_barcodes(&sample_barcodes);
         }
 
-        // If there was a sample conversion file, sort the barcodes by the sample IDs so that the columns for the merged file are in order
-        if !self.samples_barcode_hash.is_empty() {
-            sample_barcodes.sort_by_key(|barcode| {
-                self.samples_barcode_hash
-                    .get(barcode)
-                    .unwrap_or(&unknown_sample)
-            })
-        }
-
         // create the directory variable to join the file to
         let output_dir = self.args.output_dir.clone();
         let directory = Path::new(&output_dir);
 
-        let mut header = self.create_header();
-        // If merged called, create the header with the sample names as columns and write
+        let mut header = self.counts_header();
+        if !self.compound_ids.is_empty() {
+            header.push_str(",Compound_ID");
+        }
+        // If merged called, create the merged file with the sample names as columns within the header.  Rows are added after the
+        // samples are written
+        let mut merged_output = None;
         if self.args.merge_output {
             if sample_barcodes.len() == 1 {
                 eprintln!("Merged file cannot be created without multiple sample barcodes");
@@ -109,22 +123,18 @@ impl WriteFiles {
                 self.args.merge_output = false;
             } else {
                 // Create the merge file and push the header
+                let merged_file_name =
+                    self.counts_file_name(format!("{}{}", self.prefix, "_counts.all.csv"));
+                let mut merged_output_file =
+                    CountsWriter::create(&directory.join(&merged_file_name), self.args.compress)?;
                 let mut merged_header = header.clone();
                 for sample_barcode in &sample_barcodes {
-                    let sample_name = if self.samples_barcode_hash.is_empty() {
-                        sample_barcode
-                    } else {
-                        // Get the sample name from the sample barcode
-                        self
-                            .samples_barcode_hash
-                            .get(sample_barcode)
-                            .unwrap_or(&unknown_sample)
-                    };
                     merged_header.push(',');
-                    merged_header.push_str(sample_name);
+                    merged_header.push_str(&csv_field(self.sample_name(sample_barcode)));
                 }
                 merged_header.push('\n');
-                self.merge_text.push_str(&merged_header);
+                merged_output_file.write_all(merged_header.as_bytes())?;
+                merged_output = Some((merged_file_name, merged_output_file));
             }
         }
 
@@ -133,43 +143,31 @@ impl WriteFiles {
 
         // For each sample, write the counts file
         for sample_barcode in &sample_barcodes {
-            let sample_name = if !self.samples_barcode_hash.is_empty() {
-                self
-                    .samples_barcode_hash
-                    .get(sample_barcode)
-                    .unwrap_or(&unknown_sample)
-            } else {
-                sample_barcode
-            };
-            let file_name = format!("{}_{}_counts.csv", self.args.prefix, sample_name);
+            let sample_name = self.sample_name(sample_barcode);
+            let file_name =
+                self.counts_file_name(format!("{}_{}_counts.csv", self.prefix, sample_name));
             println!("{}", file_name);
             self.output_files.push(file_name.clone());
             // join the filename with the directory to create the full path
             let output_path = directory.join(file_name);
 
-            self.sample_text.push_str(&header);
+            let mut output = CountsWriter::create(&output_path, self.args.compress)?; // Create the output file
+            output.write_all(header.as_bytes())?;
             let count =
-                self.add_counts_string(sample_barcode, &sample_barcodes, EnrichedType::Full)?;
-
-            let mut output = File::create(output_path)?; // Create the output file
-            output.write_all(self.sample_text.as_bytes())?;
-            self.sample_text.clear();
+                self.add_counts_string(sample_barcode, EnrichedType::Full, &mut output)?;
+            output.finish()?;
             self.output_counts.push(count);
         }
-        if self.args.merge_output {
-            let merged_file_name = format!("{}{}", self.args.prefix, "_counts.all.csv");
+        if let Some((merged_file_name, mut merged_output_file)) = merged_output {
             println!("{}", merged_file_name);
-            println!(
-                "Barcodes counted: {}",
-                self.merged_count.to_formatted_string(&Locale::en)
-            );
-            self.output_files.push(merged_file_name.clone());
-            let merged_output_path = directory.join(merged_file_name);
-            let mut merged_output_file = File::create(merged_output_path)?;
-            merged_output_file.write_all(self.merge_text.as_bytes())?;
-            self.merge_text.clear();
-            self.output_counts.insert(0, self.merged_count);
-            self.merged_count = 0;
+            let merged_count = self.add_merged_counts_string(
+                &sample_barcodes,
+                &EnrichedType::Full,
+                &mut merged_output_file,
+            )?;
+            merged_output_file.finish()?;
+            self.output_files.push(merged_file_name);
+            self.output_counts.push(merged_count);
         }
         if self.args.enrich {
             self.write_enriched_files(EnrichedType::Single)?;
@@ -180,96 +178,445 @@ impl WriteFiles {
         Ok(())
     }
 
+    /// Writes the sample counts, and the merged counts when merge_output is set, as Arrow IPC files.  Barcode and compound ID columns
+    /// are strings and counts are unsigned integers so that the types are kept when loaded.  Rows are in the same order as the CSV
+    /// counts files.  The sample ID, sample barcode, and sample file metadata, as 'sample.<header>', are stored within the schema
+    /// metadata
+    pub fn write_arrow_files(&mut self) -> Result<()> {
+        let sample_barcodes = self.sorted_sample_barcodes();
+
+        // create the directory variable to join the file to
+        let output_dir = self.args.output_dir.clone();
+        let directory = Path::new(&output_dir);
+        let with_compound_ids = !self.compound_ids.is_empty();
+        let barcode_headers = self.counts_headers();
+
+        for sample_barcode in &sample_barcodes {
+            let sample_name = self.sample_name(sample_barcode).to_string();
+            let file_name = format!("{}_{}_counts.arrow", self.prefix, sample_name);
+            println!("{}", file_name);
+            // Sample file metadata is prefixed so that it cannot replace the keys written for every file
+            let mut metadata = self.arrow_metadata();
+            metadata.extend(
+                self.sample_metadata_map(&sample_name)
+                    .into_iter()
+                    .map(|(header, value)| (format!("sample.{}", header), value)),
+            );
+            metadata.insert("sample_id".to_string(), sample_name);
+            metadata.insert("sample_barcode".to_string(), sample_barcode.clone());
+
+            let mut output = ArrowCountsWriter::create(
+                &directory.join(&file_name),
+                &barcode_headers,
+                with_compound_ids,
+                &["Count".to_string()],
+                metadata,
+            )?;
+            let codes = self.sorted_codes(sample_barcode, &EnrichedType::Full);
+            for code in &codes {
+                output.add_row(
+                    &self.barcode_values(code),
+                    self.compound_ids.get(code),
+                    &[self.code_count(sample_barcode, code, &EnrichedType::Full) as u64],
+                )?;
+            }
+            output.finish()?;
+            println!(
+                "Barcodes counted: {}",
+                codes.len().to_formatted_string(&Locale::en)
+            );
+            self.output_files.push(file_name);
+            self.output_counts.push(codes.len());
+        }
+
+        // merge_output is turned off by write_counts_files when there is only one sample
+        if self.args.merge_output {
+            let file_name = format!("{}_counts.all.arrow", self.prefix);
+            println!("{}", file_name);
+            let sample_names = sample_barcodes
+                .iter()
+                .map(|sample_barcode| self.sample_name(sample_barcode).to_string())
+                .collect::<Vec<String>>();
+            // Store which sample barcode each sample column is for as a JSON object of sample ID to sample barcode
+            let mut metadata = self.arrow_metadata();
+            metadata.insert(
+                "sample_barcodes".to_string(),
+                serde_json::to_string(
+                    &sample_names
+                        .iter()
+                        .zip(&sample_barcodes)
+                        .collect::<std::collections::BTreeMap<&String, &String>>(),
+                )?,
+            );
+
+            let mut output = ArrowCountsWriter::create(
+                &directory.join(&file_name),
+                &barcode_headers,
+                with_compound_ids,
+                &sample_names,
+                metadata,
+            )?;
+            // Rows are in the same order as the merged CSV file
+            let codes = self.merged_codes(&sample_barcodes, &EnrichedType::Full);
+            for code in &codes {
+                let counts = sample_barcodes
+                    .iter()
+                    .map(|sample_barcode| {
+                        self.code_count(sample_barcode, code, &EnrichedType::Full) as u64
+                    })
+                    .collect::<Vec<u64>>();
+                output.add_row(
+                    &self.barcode_values(code),
+                    self.compound_ids.get(code),
+                    &counts,
+                )?;
+            }
+            output.finish()?;
+            println!(
+                "Barcodes counted: {}",
+                codes.len().to_formatted_string(&Locale::en)
+            );
+            self.output_files.push(file_name);
+            self.output_counts.push(codes.len());
+        }
+        Ok(())
+    }
+
+    /// Writes the counts of every sample to one long format file, with a row for each sample and counted barcode combination instead
+    /// of a column for each sample, which is smaller when most combinations are not within most samples.  With a random barcode,
+    /// Count is the number of reads and UMI_Count is the number of unique random barcodes
+    pub fn write_long_file(&mut self) -> Result<()> {
+        let sample_barcodes = self.sorted_sample_barcodes();
+
+        let mut columns = vec!["Sample".to_string()];
+        columns.extend(self.sample_metadata_headers.iter().cloned());
+        columns.extend(self.counts_headers());
+        if !self.compound_ids.is_empty() {
+            columns.push("Compound_ID".to_string());
+        }
+        columns.push("Count".to_string());
+        if self.sequence_format.random_barcode {
+            columns.push("UMI_Count".to_string());
+        }
+        // A sample file column named the same as another column would make the columns ambiguous when read
+        if let Some(duplicate) = duplicate_column(&columns) {
+            return Err(anyhow!(
+                "The long counts file would have more than one '{}' column.  Rename it within the sample file or format file",
+                duplicate
+            ));
+        }
+        let file_name = self.counts_file_name(format!("{}_counts.long.csv", self.prefix));
+        println!("{}", file_name);
+        let output_path = Path::new(&self.args.output_dir).join(&file_name);
+        let mut output = CountsWriter::create(&output_path, self.args.compress)?;
+        writeln!(
+            output,
+            "{}",
+            columns.iter().map(|column| csv_field(column)).join(",")
+        )?;
+
+        let mut rows = 0;
+        for sample_barcode in &sample_barcodes {
+            // Follow the sample with its metadata, which is empty for samples not within the sample file
+            let mut sample_columns = csv_field(self.sample_name(sample_barcode)).into_owned();
+            for metadata_value in self.sample_metadata_values(sample_barcode) {
+                sample_columns.push(',');
+                sample_columns.push_str(&csv_field(metadata_value));
+            }
+            for code in self.sorted_codes(sample_barcode, &EnrichedType::Full) {
+                write!(output, "{},{}", sample_columns, self.barcode_columns(&code))?;
+                if !self.compound_ids.is_empty() {
+                    write!(
+                        output,
+                        ",{}",
+                        csv_field(
+                            self.compound_ids
+                                .get(&code)
+                                .map_or("", |compound_id| compound_id.as_str())
+                        )
+                    )?;
+                }
+                write!(
+                    output,
+                    ",{}",
+                    self.results.read_count(sample_barcode, &code)
+                )?;
+                if self.sequence_format.random_barcode {
+                    write!(
+                        output,
+                        ",{}",
+                        self.code_count(sample_barcode, &code, &EnrichedType::Full)
+                    )?;
+                }
+                writeln!(output)?;
+                rows += 1;
+            }
+        }
+        output.finish()?;
+        println!(
+            "Barcodes counted: {}",
+            rows.to_formatted_string(&Locale::en)
+        );
+        self.output_files.push(file_name);
+        self.output_counts.push(rows);
+        Ok(())
+    }
+
+    /// Writes the counts of every sample as a Matrix Market sparse matrix, with a row for each counted barcode combination and a
+    /// column for each sample.  Follows the Cell Ranger v3 layout of a directory with gzipped matrix.mtx, features.tsv, and
+    /// barcodes.tsv files, where the features are the barcode combinations and the barcodes are the sample names, so that scanpy
+    /// and Seurat can read it directly
+    pub fn write_mtx_files(&mut self) -> Result<()> {
+        let sample_barcodes = self.sorted_sample_barcodes();
+
+        let matrix_dir_name = format!("{}_mtx", self.prefix);
+        println!("{}/", matrix_dir_name);
+        let matrix_dir = Path::new(&self.args.output_dir).join(&matrix_dir_name);
+        create_dir_all(&matrix_dir)?;
+
+        // Rows are in the same order as the merged file.  The number of non zero entries is needed before the entries within the
+        // matrix header
+        let codes = self.merged_codes(&sample_barcodes, &EnrichedType::Full);
+        let entries = codes
+            .iter()
+            .map(|code| {
+                sample_barcodes
+                    .iter()
+                    .filter(|sample_barcode| {
+                        self.code_count(sample_barcode, code, &EnrichedType::Full) > 0
+                    })
+                    .count()
+            })
+            .sum::<usize>();
+
+        // The features file has the barcodes as written to the counts files, the compound ID or the barcodes again as the name, and the
+        // feature type
+        let mut features =
+            CountsWriter::create(&matrix_dir.join("features.tsv.gz"), Some(Compression::Gzip))?;
+        for code in &codes {
+            let written_barcodes = self.written_barcodes(code, &EnrichedType::Full);
+            let feature_name = self
+                .compound_ids
+                .get(code)
+                .unwrap_or(&written_barcodes);
+            writeln!(
+                features,
+                "{}\t{}\tBarcode Counts",
+                written_barcodes, feature_name
+            )?;
+        }
+        features.finish()?;
+
+        let mut barcodes =
+            CountsWriter::create(&matrix_dir.join("barcodes.tsv.gz"), Some(Compression::Gzip))?;
+        for sample_barcode in &sample_barcodes {
+            writeln!(barcodes, "{}", self.sample_name(sample_barcode))?;
+        }
+        barcodes.finish()?;
+
+        // Matrix Market coordinates start at 1 and only the non zero counts are written
+        let mut matrix =
+            CountsWriter::create(&matrix_dir.join("matrix.mtx.gz"), Some(Compression::Gzip))?;
+        writeln!(matrix, "%%MatrixMarket matrix coordinate integer general")?;
+        writeln!(
+            matrix,
+            "{} {} {}",
+            codes.len(),
+            sample_barcodes.len(),
+            entries
+        )?;
+        for (row, code) in codes.iter().enumerate() {
+            for (column, sample_barcode) in sample_barcodes.iter().enumerate() {
+                let count = self.code_count(sample_barcode, code, &EnrichedType::Full);
+                if count > 0 {
+                    writeln!(matrix, "{} {} {}", row + 1, column + 1, count)?;
+                }
+            }
+        }
+        matrix.finish()?;
+        println!(
+            "Barcodes counted: {}",
+            codes.len().to_formatted_string(&Locale::en)
+        );
+        self.output_files
+            .push(format!("{}/matrix.mtx.gz", matrix_dir_name));
+        self.output_counts.push(codes.len());
+        Ok(())
+    }
+
+    /// Returns the schema metadata shared by all Arrow files of this sequence format
+    fn arrow_metadata(&self) -> std::collections::HashMap<String, String> {
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert(
+            "barcode_count_version".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        );
+        metadata.insert(
+            "format".to_string(),
+            self.sequence_format.format_string.clone(),
+        );
+        metadata
+    }
+
+    /// Returns the sample barcodes within the results, from either the random hashmap or the counts hashmap, in the order the
+    /// samples are written
+    fn sorted_sample_barcodes(&self) -> Vec<String> {
+        let mut sample_barcodes = match &self.results.results_hashmap {
+            ResultsHashmap::RandomBarcode(random_hashmap) => {
+                random_hashmap.keys().cloned().collect::<Vec<String>>()
+            }
+            ResultsHashmap::NoRandomBarcode(count_hashmap) => {
+                count_hashmap.keys().cloned().collect::<Vec<String>>()
+            }
+        };
+        self.sort_sample_barcodes(&mut sample_barcodes);
+        sample_barcodes
+    }
+
+    /// Sorts the sample barcodes, then by the sample IDs if there was a sample conversion file, so that the samples and the columns
+    /// of the merged files are in the same order each run
+    fn sort_sample_barcodes(&self, sample_barcodes: &mut [String]) {
+        sample_barcodes.sort();
+        if !self.samples_barcode_hash.is_empty() {
+            sample_barcodes.sort_by_key(|barcode| self.sample_name(barcode).to_string())
+        }
+    }
+
+    /// Returns the sample ID of the sample barcode when there is a sample conversion file, otherwise the sample barcode itself.
+    /// Sample barcodes without an ID are named 'barcode'
+    fn sample_name<'a>(&'a self, sample_barcode: &'a str) -> &'a str {
+        if self.samples_barcode_hash.is_empty() {
+            sample_barcode
+        } else {
+            self.samples_barcode_hash
+                .get(sample_barcode)
+                .map_or("barcode", |sample_name| sample_name.as_str())
+        }
+    }
+
+    /// Returns the values of the extra sample file columns for the sample barcode, in the same order as the metadata headers.
+    /// Values are empty for samples not within the sample file
+    fn sample_metadata_values(&self, sample_barcode: &str) -> Vec<&str> {
+        match self.sample_metadata.get(self.sample_name(sample_barcode)) {
+            Some(metadata_values) => metadata_values.iter().map(|value| value.as_str()).collect(),
+            None => vec![""; self.sample_metadata_headers.len()],
+        }
+    }
+
+    /// Returns the sample file metadata of the sample name as header to value, or an empty map when there is none
+    fn sample_metadata_map(&self, sample_name: &str) -> BTreeMap<String, String> {
+        self.sample_metadata
+            .get(sample_name)
+            .map(|metadata_values| {
+                self.sample_metadata_headers
+                    .iter()
+                    .cloned()
+                    .zip(metadata_values.iter().cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Adds the compression extension to a counts file name when the counts files are compressed
+    fn counts_file_name(&self, file_name: String) -> String {
+        match self.args.compress {
+            Some(compression) => format!("{}{}", file_name, compression.extension()),
+            None => file_name,
+        }
+    }
+
     /// Creates the file header string for column headers
     fn create_header(&self) -> String {
-        // Create a comma separated header.  First columns are the barcodes, 'Barcode_#'.  The last header is 'Count'
-        let mut header = String::new();
-        if self.sequence_format.barcode_num > 1 {
-            header = "Barcode_1".to_string();
-            for num in 1..self.sequence_format.barcode_num {
-                header.push_str(&format!(",Barcode_{}", num + 1))
+        // Create a comma separated header.  First columns are the barcodes, 'Barcode_#' unless named within the format file.  The last header is 'Count'
+        self.sequence_format.barcode_names.join(",")
+    }
+
+    /// Creates the comma separated counted barcode column headers of the counts files
+    fn counts_header(&self) -> String {
+        self.counts_headers()
+            .iter()
+            .map(|header| csv_field(header))
+            .join(",")
+    }
+
+    /// Returns the counted barcode column headers of the counts files.  Each counted barcode is followed by its sequence column
+    /// when sequence_and_id is set and it has a conversion, then by the extra columns of its conversion file
+    fn counts_headers(&self) -> Vec<String> {
+        let mut headers = Vec::new();
+        for (barcode_index, barcode_name) in self.sequence_format.barcode_names.iter().enumerate() {
+            headers.push(barcode_name.clone());
+            if self.with_sequence_column(barcode_index) {
+                headers.push(format!("{}_Sequence", barcode_name));
+            }
+            if let Some(extra_headers) = self.counted_barcode_extra_headers.get(barcode_index) {
+                headers.extend(
+                    extra_headers
+                        .iter()
+                        .map(|extra_header| format!("{}_{}", barcode_name, extra_header)),
+                );
+            }
+        }
+        headers
+    }
+
+    /// Returns whether the counted barcode at barcode_index has a sequence column next to its ID
+    fn with_sequence_column(&self, barcode_index: usize) -> bool {
+        self.args.sequence_and_id
+            && self
+                .counted_barcodes_hash
+                .get(barcode_index)
+                .is_some_and(|barcode_hash| !barcode_hash.is_empty())
+    }
+
+    /// Returns the comma separated counted barcode columns of the code as written to the counts files, quoting any value that
+    /// contains a comma
+    fn barcode_columns(&self, code: &str) -> String {
+        self.barcode_values(code)
+            .into_iter()
+            .map(csv_field)
+            .join(",")
+    }
+
+    /// Returns the counted barcode column values of the code, in the same order as counts_headers.  Values of extra conversion
+    /// columns are empty when missing from the conversion file
+    fn barcode_values<'a>(&'a self, code: &'a str) -> Vec<&'a str> {
+        if self.counted_barcodes_hash.is_empty() {
+            return code.split(',').collect();
+        }
+        let mut values = Vec::new();
+        for (barcode_index, barcode) in code.split(',').enumerate() {
+            let barcode_hash = &self.counted_barcodes_hash[barcode_index];
+            if barcode_hash.is_empty() {
+                values.push(barcode);
+            } else {
+                values.push(barcode_hash.get(barcode).unwrap().as_str());
+                if self.args.sequence_and_id {
+                    values.push(barcode);
+                }
+            }
+            let extra_values_option = self.counted_barcode_extras[barcode_index].get(barcode);
+            for extra_index in 0..self.counted_barcode_extra_headers[barcode_index].len() {
+                values.push(
+                    extra_values_option
+                        .map_or("", |extra_values| extra_values[extra_index].as_str()),
+                );
             }
-        } else {
-            header.push_str("Barcode")
         }
-        header
+        values
     }
 
-    /// Writes the files for when a random barcode is not included
+    /// Writes the rows of the sample to its counts file.  Returns the number of rows written for the sample
     fn add_counts_string(
         &mut self,
         sample_barcode: &str,
-        sample_barcodes: &[String],
         enrichment: EnrichedType, // In order to make this non redundant with writing single and double barcodes, this enum determines some aspects
+        output: &mut CountsWriter,
     ) -> Result<usize> {
-        let mut hash_holder: HashMap<String, HashMap<String, usize>> = HashMap::new(); // a hodler hash to hold the hashmap from sample_counts_hash for a longer lifetime.  Also used later
-                                                                                       // Select from the hashmap connected the the EnrichedType
-        let codes = match enrichment {
-            EnrichedType::Single => {
-                hash_holder = self.results_enriched.single_hashmap.clone();
-                hash_holder
-                    .get(sample_barcode)
-                    .unwrap()
-                    .keys()
-                    .cloned()
-                    .collect::<Vec<String>>()
-            }
-            EnrichedType::Double => {
-                hash_holder = self.results_enriched.double_hashmap.clone();
-                hash_holder
-                    .get(sample_barcode)
-                    .unwrap()
-                    .keys()
-                    .cloned()
-                    .collect::<Vec<String>>()
-            }
-            EnrichedType::Full => match &self.results.results_hashmap {
-                ResultsHashmap::NoRandomBarcode(count_hashmap) => count_hashmap
-                    .get(sample_barcode)
-                    .unwrap()
-                    .keys()
-                    .cloned()
-                    .collect::<Vec<String>>(),
-                ResultsHashmap::RandomBarcode(random_hashmap) => random_hashmap
-                    .get(sample_barcode)
-                    .unwrap()
-                    .keys()
-                    .cloned()
-                    .collect::<Vec<String>>(),
-            },
-        };
+        let codes = self.sorted_codes(sample_barcode, &enrichment);
 
         let mut barcode_num = 0;
         for (line_num, code) in codes.iter().enumerate() {
-            let count = match enrichment {
-                EnrichedType::Single => *self
-                    .results_enriched
-                    .single_hashmap
-                    .get(sample_barcode)
-                    .unwrap()
-                    .get(code)
-                    .unwrap(),
-                EnrichedType::Double => *self
-                    .results_enriched
-                    .double_hashmap
-                    .get(sample_barcode)
-                    .unwrap()
-                    .get(code)
-                    .unwrap(),
-                EnrichedType::Full => match &self.results.results_hashmap {
-                    ResultsHashmap::NoRandomBarcode(count_hashmap) => *count_hashmap
-                        .get(sample_barcode)
-                        .unwrap()
-                        .get(code)
-                        .unwrap(),
-                    ResultsHashmap::RandomBarcode(random_hashmap) => random_hashmap
-                        .get(sample_barcode)
-                        .unwrap()
-                        .get(code)
-                        .unwrap()
-                        .len(),
-                },
-            };
+            let count = self.code_count(sample_barcode, code, &enrichment);
             barcode_num = line_num + 1;
             // Print the number counted so far ever 50,000 writes
             if barcode_num % 50000 == 0 {
@@ -279,71 +626,14 @@ impl WriteFiles {
                 );
                 stdout().flush()?;
             }
-            let written_barcodes = if enrichment == EnrichedType::Full && !self.counted_barcodes_hash.is_empty() {
-                // Convert the building block DNA barcodes and join them back to comma separated
-                convert_code(code, &self.counted_barcodes_hash)
-            } else {
-                code.to_string()
-            };
-
-            // If merge output argument is called, pull data for the compound and write to merged file
-            if self.args.merge_output {
-                // If the compound has not already been written to the file proceed.  This will happen after the first sample is completed
-                let new = self.compounds_written.insert(code.to_string());
-                if new {
-                    self.merged_count += 1;
-                    // Start a new row with the converted building block barcodes
-                    let mut merged_row = written_barcodes.clone();
-                    // For every sample, retrieve the count and add to the row with a comma
-                    for sample_barcode in sample_barcodes {
-                        merged_row.push(',');
-                        // Get teh sample count from the hashmap that corresponds to the EnrichedType.  For single and double, it is the holding hashmap created earlier
-                        let sample_count = match enrichment {
-                            EnrichedType::Single => hash_holder
-                                .get(sample_barcode)
-                                .unwrap()
-                                .get(code)
-                                .unwrap_or(&0)
-                                .to_string(),
-
-                            EnrichedType::Double => hash_holder
-                                .get(sample_barcode)
-                                .unwrap()
-                                .get(code)
-                                .unwrap_or(&0)
-                                .to_string(),
-
-                            EnrichedType::Full => match &self.results.results_hashmap {
-                                ResultsHashmap::RandomBarcode(random_hashmap) => random_hashmap
-                                    .get(sample_barcode)
-                                    .unwrap()
-                                    .get(code)
-                                    .unwrap_or(&AHashSet::new())
-                                    .len()
-                                    .to_string(),
-                                ResultsHashmap::NoRandomBarcode(count_hashmap) => count_hashmap
-                                    .get(sample_barcode)
-                                    .unwrap()
-                                    .get(code)
-                                    .unwrap_or(&0)
-                                    .to_string(),
-                            },
-                        };
-                        merged_row.push_str(&sample_count);
-                    }
-                    merged_row.push('\n');
-                    // write to the merged file
-                    self.merge_text.push_str(&merged_row);
-                }
-            }
             // Create the row for the sample file and write
-            let row = format!("{},{}\n", written_barcodes, count);
-            self.sample_text.push_str(&row);
+            writeln!(output, "{},{}", self.code_columns(code, &enrichment), count)?;
             // If enrichment type is Full, which is neither single nor double for adding string,
             // and enrich is called.  Add 1 and 2 synthon enrichment.  This is becuase this smae
             // method is called to create the 1 and 2 synthon strings, and therefore should only
             // run when Full is used
             if enrichment == EnrichedType::Full && self.args.enrich {
+                let written_barcodes = self.written_barcodes(code, &enrichment);
                 self.results_enriched
                     .add_single(sample_barcode, &written_barcodes, count);
                 if self.sequence_format.barcode_num > 2 {
@@ -360,9 +650,229 @@ impl WriteFiles {
         Ok(barcode_num)
     }
 
+    /// Writes a row to the merged file for the codes of every sample, with the count of each sample.  Rows are sorted over all of
+    /// the samples, using the total count with the count sort order.  Returns the number of rows written
+    fn add_merged_counts_string(
+        &self,
+        sample_barcodes: &[String],
+        enrichment: &EnrichedType,
+        merged_output: &mut CountsWriter,
+    ) -> Result<usize> {
+        let codes = self.merged_codes(sample_barcodes, enrichment);
+        for code in &codes {
+            // Start a new row with the converted building block barcodes
+            let mut merged_row = self.code_columns(code, enrichment);
+            // For every sample, retrieve the count and add to the row with a comma
+            for sample_barcode in sample_barcodes {
+                merged_row.push(',');
+                merged_row.push_str(
+                    &self
+                        .code_count(sample_barcode, code, enrichment)
+                        .to_string(),
+                );
+            }
+            merged_row.push('\n');
+            merged_output.write_all(merged_row.as_bytes())?;
+        }
+        println!(
+            "Barcodes counted: {}",
+            codes.len().to_formatted_string(&Locale::en)
+        );
+        Ok(codes.len())
+    }
+
+    /// Returns the barcode columns of the code as written to the counts files, followed by the compound ID when there are compound
+    /// IDs.  Single and double enrichment codes are only the barcodes
+    fn code_columns(&self, code: &str, enrichment: &EnrichedType) -> String {
+        if *enrichment != EnrichedType::Full {
+            return self.written_barcodes(code, enrichment);
+        }
+        // Counts files can have sequence and extra conversion columns next to the barcodes
+        let mut code_columns = self.barcode_columns(code);
+        // Kept separate from the barcodes so that enrichment only sees the barcodes
+        if !self.compound_ids.is_empty() {
+            code_columns.push(',');
+            code_columns.push_str(&csv_field(
+                self.compound_ids
+                    .get(code)
+                    .map_or("", |compound_id| compound_id.as_str()),
+            ));
+        }
+        code_columns
+    }
+
+    /// Returns the codes of all samples in the order they are written to the merged files.  Sorted the same as the sample files, with
+    /// the counts summed over the samples for the count sort order
+    fn merged_codes(&self, sample_barcodes: &[String], enrichment: &EnrichedType) -> Vec<String> {
+        let mut code_set = AHashSet::new();
+        for sample_barcode in sample_barcodes {
+            code_set.extend(self.sample_codes(sample_barcode, enrichment));
+        }
+        if *enrichment == EnrichedType::Full && self.args.include_zero {
+            code_set.extend(&self.expected_combinations);
+        }
+        let total_counts = code_set
+            .iter()
+            .map(|code| {
+                let total_count = sample_barcodes
+                    .iter()
+                    .map(|sample_barcode| self.code_count(sample_barcode, code, enrichment))
+                    .sum::<usize>();
+                (code.as_str(), total_count)
+            })
+            .collect::<HashMap<&str, usize>>();
+        let mut codes = code_set.into_iter().collect::<Vec<&String>>();
+        self.sort_codes(&mut codes, enrichment, |code| total_counts[code]);
+        codes.into_iter().cloned().collect()
+    }
+
+    /// Returns the codes counted for the sample in no particular order
+    fn sample_codes(&self, sample_barcode: &str, enrichment: &EnrichedType) -> Vec<&String> {
+        match *enrichment {
+            EnrichedType::Single => self
+                .results_enriched
+                .single_hashmap
+                .get(sample_barcode)
+                .unwrap()
+                .keys()
+                .collect(),
+            EnrichedType::Double => self
+                .results_enriched
+                .double_hashmap
+                .get(sample_barcode)
+                .unwrap()
+                .keys()
+                .collect(),
+            EnrichedType::Full => match &self.results.results_hashmap {
+                ResultsHashmap::NoRandomBarcode(count_hashmap) => {
+                    count_hashmap.get(sample_barcode).unwrap().keys().collect()
+                }
+                ResultsHashmap::RandomBarcode(random_hashmap) => {
+                    random_hashmap.get(sample_barcode).unwrap().keys().collect()
+                }
+            },
+        }
+    }
+
+    /// Returns the codes counted for the sample in the order they are written.  Includes the expected library members when
+    /// include_zero is set, and sorts by the sort order so that the same input always writes the same file
+    fn sorted_codes(&self, sample_barcode: &str, enrichment: &EnrichedType) -> Vec<String> {
+        let mut codes = self
+            .sample_codes(sample_barcode, enrichment)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<String>>();
+
+        // Add the expected library members so that members with no counts are included
+        if *enrichment == EnrichedType::Full && self.args.include_zero {
+            let expected_codes = self
+                .expected_combinations
+                .iter()
+                .collect::<AHashSet<&String>>();
+            let unexpected_codes = codes
+                .into_iter()
+                .filter(|code| !expected_codes.contains(code))
+                .collect::<Vec<String>>();
+            codes = self.expected_combinations.clone();
+            codes.extend(unexpected_codes);
+        }
+
+        self.sort_codes(&mut codes, enrichment, |code| {
+            self.code_count(sample_barcode, code, enrichment)
+        });
+        codes
+    }
+
+    /// Sorts the codes by the sort order so that the same input always writes the same file.  Ties are broken by ID and then
+    /// sequence.  count returns the count of a code used with the count sort order
+    fn sort_codes<C, F>(&self, codes: &mut [C], enrichment: &EnrichedType, count: F)
+    where
+        C: AsRef<str> + Ord,
+        F: Fn(&str) -> usize,
+    {
+        match self.args.sort {
+            SortOrder::Count => codes.sort_unstable_by(|first, second| {
+                count(second.as_ref())
+                    .cmp(&count(first.as_ref()))
+                    .then_with(|| self.compare_ids(first.as_ref(), second.as_ref(), enrichment))
+            }),
+            SortOrder::Id => {
+                codes.sort_unstable_by(|first, second| {
+                    self.compare_ids(first.as_ref(), second.as_ref(), enrichment)
+                })
+            }
+            SortOrder::Sequence => codes.sort_unstable(),
+        }
+    }
+
+    /// Compares two codes by the IDs of their counted barcodes, then by their sequences.  Barcodes without a conversion are compared
+    /// by their sequence
+    fn compare_ids(&self, first: &str, second: &str, enrichment: &EnrichedType) -> cmp::Ordering {
+        if *enrichment != EnrichedType::Full || self.counted_barcodes_hash.is_empty() {
+            return first.cmp(second);
+        }
+        self.barcode_ids(first)
+            .cmp(self.barcode_ids(second))
+            .then_with(|| first.cmp(second))
+    }
+
+    /// Returns the ID of each counted barcode within the code, or the sequence for barcodes without a conversion
+    fn barcode_ids<'a>(&'a self, code: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        code.split(',')
+            .zip(&self.counted_barcodes_hash)
+            .map(|(barcode, barcode_hash)| {
+                barcode_hash
+                    .get(barcode)
+                    .map_or(barcode, |barcode_id| barcode_id.as_str())
+            })
+    }
+
+    /// Returns the count of the barcodes within code for the sample, or 0 if they were not counted.  Random barcode counts are the
+    /// number of unique random barcodes
+    fn code_count(&self, sample_barcode: &str, code: &str, enrichment: &EnrichedType) -> usize {
+        match enrichment {
+            EnrichedType::Single => *self
+                .results_enriched
+                .single_hashmap
+                .get(sample_barcode)
+                .unwrap()
+                .get(code)
+                .unwrap_or(&0),
+            EnrichedType::Double => *self
+                .results_enriched
+                .double_hashmap
+                .get(sample_barcode)
+                .unwrap()
+                .get(code)
+                .unwrap_or(&0),
+            EnrichedType::Full => match &self.results.results_hashmap {
+                ResultsHashmap::NoRandomBarcode(count_hashmap) => *count_hashmap
+                    .get(sample_barcode)
+                    .unwrap()
+                    .get(code)
+                    .unwrap_or(&0),
+                ResultsHashmap::RandomBarcode(random_hashmap) => random_hashmap
+                    .get(sample_barcode)
+                    .unwrap()
+                    .get(code)
+                    .map_or(0, |(random_barcodes, _)| random_barcodes.len()),
+            },
+        }
+    }
+
+    /// Returns the comma separated barcodes as written to the file.  Counted barcodes are converted to their IDs when there is a
+    /// counted barcode conversion.  Single and double enrichment codes are already converted
+    fn written_barcodes(&self, code: &str, enrichment: &EnrichedType) -> String {
+        if *enrichment == EnrichedType::Full && !self.counted_barcodes_hash.is_empty() {
+            // Convert the building block DNA barcodes and join them back to comma separated
+            convert_code(code, &self.counted_barcodes_hash)
+        } else {
+            code.to_string()
+        }
+    }
+
     /// Write enriched files for either single or double barcodes if either flag is called
     fn write_enriched_files(&mut self, enrichment: EnrichedType) -> Result<()> {
-        let unknown_sample = "barcode".to_string();
         // Pull all sample IDs from either single or double hashmap, which was added to in either random or counts write
         let mut sample_barcodes = match enrichment {
             EnrichedType::Single => self
@@ -384,14 +894,7 @@ impl WriteFiles {
             }
         };
 
-        // If there was a sample conversion file, sort the barcodes by the sample IDs so that the columns for the merged file are in order
-        if !self.samples_barcode_hash.is_empty() {
-            sample_barcodes.sort_by_key(|barcode| {
-                self.samples_barcode_hash
-                    .get(barcode)
-                    .unwrap_or(&unknown_sample)
-            })
-        }
+        self.sort_sample_barcodes(&mut sample_barcodes);
 
         // Create a descriptor for output file names
         let descriptor = match enrichment {
@@ -409,24 +912,22 @@ impl WriteFiles {
         let directory = Path::new(&output_dir);
 
         let mut header = self.create_header();
-        // If merged called, create the header with the sample names as columns and write
+        // If merged called, create the merged file with the sample names as columns within the header.  Rows are added after the
+        // samples are written
+        let mut merged_output = None;
         if self.args.merge_output {
+            let merged_file_name =
+                self.counts_file_name(format!("{}_counts.all.{}.csv", self.prefix, descriptor));
+            let mut merged_output_file =
+                CountsWriter::create(&directory.join(&merged_file_name), self.args.compress)?;
             let mut merged_header = header.clone();
             for sample_barcode in &sample_barcodes {
-                let sample_name = if self.samples_barcode_hash.is_empty() {
-                    sample_barcode
-                } else {
-                    // Get the sample name from the sample barcode
-                    self
-                        .samples_barcode_hash
-                        .get(sample_barcode)
-                        .unwrap_or(&unknown_sample)
-                };
                 merged_header.push(',');
-                merged_header.push_str(sample_name);
+                merged_header.push_str(&csv_field(self.sample_name(sample_barcode)));
             }
             merged_header.push('\n');
-            self.merge_text.push_str(&merged_header);
+            merged_output_file.write_all(merged_header.as_bytes())?;
+            merged_output = Some((merged_file_name, merged_output_file));
         }
 
         // Crate the header to be used with each sample file.  This is just Barcode_1..Barcode_n and Count
@@ -435,52 +936,381 @@ impl WriteFiles {
         // For each sample, write the enriched file
         for sample_barcode in &sample_barcodes {
             // Create the file_name with the single or double descriptor
-            let sample_name = if !self.samples_barcode_hash.is_empty() {
-                self
-                    .samples_barcode_hash
-                    .get(sample_barcode)
-                    .unwrap_or(&unknown_sample)
-            } else {
-                sample_barcode
-            };
-            let file_name = format!(
+            let sample_name = self.sample_name(sample_barcode);
+            let file_name = self.counts_file_name(format!(
                 "{}_{}_counts.{}.csv",
-                self.args.prefix, sample_name, descriptor
-            );
+                self.prefix, sample_name, descriptor
+            ));
             println!("{}", file_name);
             self.output_files.push(file_name.clone());
             // join the filename with the directory to create the full path
             let output_path = directory.join(file_name);
 
-            self.sample_text.push_str(&header);
-            let count =
-                self.add_counts_string(sample_barcode, &sample_barcodes, enrichment.clone())?;
-            let mut output = File::create(output_path)?; // Create the output file
-            output.write_all(self.sample_text.as_bytes())?;
-            self.sample_text.clear();
+            let mut output = CountsWriter::create(&output_path, self.args.compress)?; // Create the output file
+            output.write_all(header.as_bytes())?;
+            let count = self.add_counts_string(sample_barcode, enrichment.clone(), &mut output)?;
+            output.finish()?;
             // add the counts to output to stats later
             self.output_counts.push(count);
         }
         // Add the count of merged barcodes if the flag is called
-        if self.args.merge_output {
-            // Create the merge file and push the header, if merged called within arguments
-            let merged_file_name = format!("{}_counts.all.{}.csv", self.args.prefix, descriptor);
+        if let Some((merged_file_name, mut merged_output_file)) = merged_output {
             println!("{}", merged_file_name);
-            self.output_files.push(merged_file_name.clone());
-            let merged_output_path = directory.join(merged_file_name);
-            let mut merged_output_file = File::create(merged_output_path)?;
-            merged_output_file.write_all(self.merge_text.as_bytes())?;
-            println!(
-                "Barcodes counted: {}",
-                self.merged_count.to_formatted_string(&Locale::en)
-            );
-            self.merge_text.clear();
-            self.output_counts.insert(
-                self.output_counts.len() - sample_barcodes.len(),
-                self.merged_count,
-            );
-            self.merged_count = 0;
+            let merged_count = self.add_merged_counts_string(
+                &sample_barcodes,
+                &enrichment,
+                &mut merged_output_file,
+            )?;
+            merged_output_file.finish()?;
+            self.output_files.push(merged_file_name);
+            self.output_counts.push(merged_count);
+        }
+        Ok(())
+    }
+
+    /// Writes the number of constant region mismatches at each constant nucleotide of the format string, over the reads that
+    /// needed their constant region corrected.  Positions start at 1 and include the barcodes within the format string
+    pub fn write_constant_mismatch_file(&self, seq_errors: &SequenceErrors) -> Result<()> {
+        let mismatch_profile = seq_errors.constant_mismatch_profile();
+        let corrected = mismatch_profile.corrected();
+        let mut profile_text =
+            "Position,Nucleotide,Mismatches,Percent_Corrected_Reads\n".to_string();
+        for (position, (nucleotide, mismatches)) in self
+            .sequence_format
+            .format_string
+            .chars()
+            .zip(mismatch_profile.position_counts())
+            .enumerate()
+        {
+            // Barcode and 'N' positions are never mismatches
+            if nucleotide == 'N' {
+                continue;
+            }
+            let percent = if corrected > 0 {
+                mismatches as f64 / corrected as f64 * 100.0
+            } else {
+                0.0
+            };
+            profile_text.push_str(&format!(
+                "{},{},{},{:.2}\n",
+                position + 1,
+                nucleotide,
+                mismatches,
+                percent
+            ));
         }
+
+        let file_name = self.constant_mismatch_file_name();
+        println!("{}", file_name);
+        println!(
+            "Corrected constant regions: {}",
+            corrected.to_formatted_string(&Locale::en)
+        );
+        let output_path = Path::new(&self.args.output_dir).join(file_name);
+        let mut output = File::create(output_path)?;
+        output.write_all(profile_text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the most frequent raw sample barcodes from reads that could not be assigned to a sample, similar to the undetermined
+    /// barcodes of bcl2fastq.  Only written when there is a sample barcode conversion file to check sample barcodes against
+    pub fn write_undetermined_sample_file(&self, seq_errors: &SequenceErrors) -> Result<()> {
+        if !self.sequence_format.sample_barcode || self.samples_barcode_hash.is_empty() {
+            return Ok(());
+        }
+        let undetermined_sample_barcodes = seq_errors.undetermined_sample_barcodes();
+        let undetermined_total = undetermined_sample_barcodes.total();
+        let mut undetermined_text = "Sample_Barcode,Count,Percent_Undetermined\n".to_string();
+        for (sample_barcode, count) in undetermined_sample_barcodes.top(self.args.top_barcodes) {
+            undetermined_text.push_str(&format!(
+                "{},{},{:.2}\n",
+                sample_barcode,
+                count,
+                count as f64 / undetermined_total as f64 * 100.0
+            ));
+        }
+
+        let file_name = format!("{}_undetermined_sample_barcodes.csv", self.prefix);
+        println!("{}", file_name);
+        let output_path = Path::new(&self.args.output_dir).join(file_name);
+        File::create(output_path)?.write_all(undetermined_text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the most frequent raw counted barcodes that could not be fixed for each counted barcode, along with the closest known
+    /// barcodes and their distance.  Tied closest barcodes are separated by ';'.  Only written when there is a counted barcode
+    /// conversion file
+    pub fn write_unknown_barcode_file(&self, seq_errors: &SequenceErrors) -> Result<()> {
+        if self.counted_barcodes_hash.is_empty() {
+            return Ok(());
+        }
+        let mut unknown_text =
+            "Barcode,Sequence,Count,Nearest_Sequence,Nearest_ID,Distance\n".to_string();
+        for ((barcode_name, barcode_hash), unknown_barcodes) in self
+            .sequence_format
+            .barcode_names
+            .iter()
+            .zip(self.counted_barcodes_hash.iter())
+            .zip(seq_errors.unknown_barcodes())
+        {
+            let known_barcodes = barcode_hash.keys().cloned().collect::<AHashSet<String>>();
+            for (sequence, count) in unknown_barcodes.top(self.args.top_barcodes) {
+                let (nearest_sequences, nearest_ids, distance) =
+                    match nearest_barcodes(&sequence, &known_barcodes) {
+                        Some((distance, nearest)) => (
+                            nearest.join(";"),
+                            nearest
+                                .iter()
+                                .map(|nearest_barcode| barcode_hash[nearest_barcode].clone())
+                                .join(";"),
+                            distance.to_string(),
+                        ),
+                        None => (String::new(), String::new(), String::new()),
+                    };
+                unknown_text.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    csv_field(barcode_name),
+                    sequence,
+                    count,
+                    nearest_sequences,
+                    csv_field(&nearest_ids),
+                    distance
+                ));
+            }
+        }
+
+        let file_name = format!("{}_unknown_barcodes.csv", self.prefix);
+        println!("{}", file_name);
+        let output_path = Path::new(&self.args.output_dir).join(file_name);
+        File::create(output_path)?.write_all(unknown_text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes MultiQC custom content files.  One is a bar graph of the counted reads and each reason reads were not counted, and the
+    /// other is a bar graph of the counted reads for each sample.  The section IDs are the same for every run so that MultiQC
+    /// combines runs into one plot
+    pub fn write_multiqc_files(&self, seq_errors: &SequenceErrors) -> Result<()> {
+        let directory = Path::new(&self.args.output_dir);
+        let error_counts = seq_errors.counts();
+
+        let mut reads_text = "\
+            # id: 'barcode_count_reads'\n\
+            # section_name: 'Barcode-count read assignment'\n\
+            # description: 'Reads counted by barcode-count and the reasons the remaining reads were not counted'\n\
+            # plot_type: 'bargraph'\n\
+            # pconfig:\n\
+            #     id: 'barcode_count_reads_plot'\n\
+            #     title: 'Barcode-count: Read Assignment'\n\
+            #     ylab: 'Reads'\n\
+            Sample\tCounted\tDuplicates\tLow quality\tConstant region mismatch\tConstant region ambiguous\tSample barcode mismatch\tSample barcode ambiguous\tCounted barcode mismatch\tCounted barcode ambiguous\tImpossible combination\n"
+            .to_string();
+        reads_text.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            self.prefix,
+            error_counts.matched,
+            error_counts.duplicates,
+            error_counts.low_quality,
+            error_counts.constant_region_mismatches,
+            error_counts.constant_region_ambiguous,
+            error_counts.sample_barcode_mismatches,
+            error_counts.sample_barcode_ambiguous,
+            error_counts.counted_barcode_mismatches,
+            error_counts.counted_barcode_ambiguous,
+            error_counts.impossible_combinations
+        ));
+        let reads_file_name = format!("{}_barcode_count_reads_mqc.tsv", self.prefix);
+        println!("{}", reads_file_name);
+        File::create(directory.join(reads_file_name))?.write_all(reads_text.as_bytes())?;
+
+        // Use the same sample names as the counts files, with the prefix, so that samples from different runs stay separate
+        let mut sample_counts = self
+            .results
+            .sample_read_counts()
+            .into_iter()
+            .map(|(sample_barcode, count)| {
+                let sample_name = self
+                    .samples_barcode_hash
+                    .get(&sample_barcode)
+                    .cloned()
+                    .unwrap_or(sample_barcode);
+                (format!("{}_{}", self.prefix, sample_name), count)
+            })
+            .collect::<Vec<(String, usize)>>();
+        sample_counts.sort();
+        let mut samples_text = "\
+            # id: 'barcode_count_samples'\n\
+            # section_name: 'Barcode-count sample reads'\n\
+            # description: 'Reads counted by barcode-count for each sample'\n\
+            # plot_type: 'bargraph'\n\
+            # pconfig:\n\
+            #     id: 'barcode_count_samples_plot'\n\
+            #     title: 'Barcode-count: Counted Reads per Sample'\n\
+            #     ylab: 'Reads'\n\
+            Sample\tCounted\n"
+            .to_string();
+        for (sample_name, count) in sample_counts {
+            samples_text.push_str(&format!("{}\t{}\n", sample_name, count));
+        }
+        let samples_file_name = format!("{}_barcode_count_samples_mqc.tsv", self.prefix);
+        println!("{}", samples_file_name);
+        File::create(directory.join(samples_file_name))?.write_all(samples_text.as_bytes())?;
+        Ok(())
+    }
+
+    /// Returns the read outcome counts for each sample, named as within the counts files and sorted by name, followed by the
+    /// undetermined reads
+    fn sample_read_rows(&self, seq_errors: &SequenceErrors) -> Vec<(String, SampleReadCounts)> {
+        let sample_reads = seq_errors.sample_reads();
+        let mut sample_rows = sample_reads
+            .sample_counts()
+            .into_iter()
+            .map(|(sample_barcode, counts)| {
+                let sample_name = self
+                    .samples_barcode_hash
+                    .get(&sample_barcode)
+                    .cloned()
+                    .unwrap_or(sample_barcode);
+                (sample_name, counts)
+            })
+            .collect::<Vec<(String, SampleReadCounts)>>();
+        sample_rows.sort_by(|a, b| a.0.cmp(&b.0));
+        sample_rows.push(("undetermined".to_string(), sample_reads.undetermined()));
+        sample_rows
+    }
+
+    /// Returns the file name of the constant region mismatch profile
+    fn constant_mismatch_file_name(&self) -> String {
+        format!("{}_constant_mismatches.csv", self.prefix)
+    }
+
+    /// Writes a JSON report of the run.  Contains the same information as the stats file, following the schema within the report
+    /// module, so that it can be read by other programs
+    pub fn write_json_report(
+        &self,
+        start_time: DateTime<Local>,
+        max_sequence_errors: &MaxSeqErrors,
+        seq_errors: &SequenceErrors,
+        total_reads: &Arc<AtomicU32>,
+    ) -> Result<()> {
+        let now = Local::now();
+        let elapsed_time = now - start_time;
+        let mismatch_profile = seq_errors.constant_mismatch_profile();
+        let positions = self
+            .sequence_format
+            .format_string
+            .chars()
+            .zip(mismatch_profile.position_counts())
+            .enumerate()
+            .filter(|(_, (nucleotide, _))| *nucleotide != 'N')
+            .map(|(position, (nucleotide, mismatches))| PositionReport {
+                position: position + 1,
+                nucleotide,
+                mismatches,
+            })
+            .collect();
+
+        let run_report = RunReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            tool: ToolReport {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            timing: TimingReport {
+                start: start_time.to_rfc3339(),
+                finish: now.to_rfc3339(),
+                total_seconds: elapsed_time.num_milliseconds() as f64 / 1000.0,
+            },
+            inputs: InputReport {
+                fastq: self.args.fastq.clone(),
+                format: self.args.formats[self.format_index].clone(),
+                sample_barcodes: self.args.sample_barcodes_option.clone(),
+                counted_barcodes: self
+                    .args
+                    .counted_barcodes_files
+                    .get(self.format_index)
+                    .cloned(),
+                expected_combinations: self
+                    .args
+                    .expected_combinations_files
+                    .get(self.format_index)
+                    .cloned(),
+            },
+            settings: SettingsReport {
+                strand: self.args.strand.to_string(),
+                threads: self.args.threads,
+                merge_output: self.args.merge_output,
+                enrich: self.args.enrich,
+                sort: self.args.sort.to_string(),
+                compress: self
+                    .args
+                    .compress
+                    .map(|compression| compression.to_string()),
+                arrow: self.args.arrow,
+                long: self.args.long,
+                mtx: self.args.mtx,
+                sequence_and_id: self.args.sequence_and_id,
+                include_zero: self.args.include_zero,
+                top_barcodes: self.args.top_barcodes,
+                fail_on_collision: self.args.fail_on_collision,
+                multiqc: self.args.multiqc,
+            },
+            format: FormatReport {
+                format_string: self.sequence_format.format_string.clone(),
+                regions_string: self.sequence_format.regions_string.clone(),
+                barcode_names: self.sequence_format.barcode_names.clone(),
+                barcode_lengths: self.sequence_format.barcode_lengths.clone(),
+                sample_barcode_length: self.sequence_format.sample_length_option,
+                random_barcode: self.sequence_format.random_barcode,
+            },
+            max_errors: MaxErrorsReport {
+                constant_region: max_sequence_errors.max_constant_errors(),
+                sample_barcode: max_sequence_errors.max_sample_errors(),
+                counted_barcodes: max_sequence_errors.max_barcode_errors().to_vec(),
+                min_quality: max_sequence_errors.min_quality(),
+            },
+            reads: ReadsReport {
+                total: total_reads.load(Ordering::Relaxed),
+                counts: seq_errors.counts(),
+            },
+            region_matches: seq_errors
+                .region_matches()
+                .iter()
+                .map(|region| RegionReport {
+                    region: region.name().to_string(),
+                    mismatches: region.match_counts(),
+                    failed: region.failures(),
+                })
+                .collect(),
+            samples: self
+                .sample_read_rows(seq_errors)
+                .into_iter()
+                .map(|(sample, reads)| SampleReport {
+                    metadata: self.sample_metadata_map(&sample),
+                    sample,
+                    reads,
+                })
+                .collect(),
+            constant_mismatch_profile: MismatchProfileReport {
+                file: self.constant_mismatch_file_name(),
+                corrected: mismatch_profile.corrected(),
+                positions,
+            },
+            output_files: self
+                .output_files
+                .iter()
+                .zip(self.output_counts.iter())
+                .map(|(file_name, counts)| OutputFileReport {
+                    file: file_name.clone(),
+                    barcodes_counted: *counts,
+                })
+                .collect(),
+        };
+
+        let report_path =
+            Path::new(&self.args.output_dir).join(format!("{}_report.json", self.prefix));
+        let mut report_file = File::create(report_path)?;
+        serde_json::to_writer_pretty(&mut report_file, &run_report)?;
+        report_file.write_all("\n".as_bytes())?;
         Ok(())
     }
 
@@ -496,10 +1326,9 @@ impl WriteFiles {
         // Create the stat file name
         let output_dir = self.args.output_dir.clone();
         let directory = Path::new(&output_dir);
-        let stat_filename = directory.join(format!("{}_barcode_stats.txt", self.args.prefix));
+        let stat_filename = directory.join(format!("{}_barcode_stats.txt", self.prefix));
         // Make the stat file and make it an appending function
         let mut stat_file = OpenOptions::new()
-            .write(true)
             .append(true)
             .create(true)
             .open(stat_filename)?;
@@ -525,14 +1354,14 @@ impl WriteFiles {
             format!(
                 "-INPUT FILES-\nFastq: {}\nFormat: {}\nSamples: {}\nBarcodes: {}\n\n",
                 self.args.fastq,
-                self.args.format,
+                self.args.formats[self.format_index],
                 self.args
                     .sample_barcodes_option
                     .as_ref()
                     .unwrap_or(&"None".to_string()),
                 self.args
-                    .counted_barcodes_option
-                    .as_ref()
+                    .counted_barcodes_files
+                    .get(self.format_index)
                     .unwrap_or(&"None".to_string())
             )
             .as_bytes(),
@@ -552,6 +1381,55 @@ impl WriteFiles {
             )
             .as_bytes(),
         )?;
+        // Record the read outcomes for each sample, with reads that failed before the sample was known as undetermined
+        let sample_rows = self.sample_read_rows(&seq_errors);
+        let name_width = sample_rows
+            .iter()
+            .map(|(sample_name, _)| sample_name.len())
+            .max()
+            .unwrap_or(0)
+            .max("Sample".len());
+        stat_file.write_all(
+            format!(
+                "-SAMPLE READS-\n{:<width$}  {:>12}  {:>12}  {:>12}  {:>15}  {:>14}  {:>15}  {:>22}\n",
+                "Sample",
+                "Matched",
+                "Duplicates",
+                "Low quality",
+                "Constant region",
+                "Sample barcode",
+                "Counted barcode",
+                "Impossible combination",
+                width = name_width
+            )
+            .as_bytes(),
+        )?;
+        for (sample_name, counts) in sample_rows {
+            stat_file.write_all(
+                format!(
+                    "{:<width$}  {:>12}  {:>12}  {:>12}  {:>15}  {:>14}  {:>15}  {:>22}\n",
+                    sample_name,
+                    counts.matched.to_formatted_string(&Locale::en),
+                    counts.duplicates.to_formatted_string(&Locale::en),
+                    counts.low_quality.to_formatted_string(&Locale::en),
+                    counts
+                        .constant_region_errors
+                        .to_formatted_string(&Locale::en),
+                    counts
+                        .sample_barcode_errors
+                        .to_formatted_string(&Locale::en),
+                    counts
+                        .counted_barcode_errors
+                        .to_formatted_string(&Locale::en),
+                    counts
+                        .impossible_combinations
+                        .to_formatted_string(&Locale::en),
+                    width = name_width
+                )
+                .as_bytes(),
+            )?;
+        }
+        stat_file.write_all("\n".as_bytes())?;
         // Record the files that were created
         stat_file.write_all("-OUTPUT FILES-\n".as_bytes())?;
         for (file_name, counts) in self.output_files.iter().zip(self.output_counts.iter()) {
@@ -576,6 +1454,170 @@ impl WriteFiles {
     }
 }
 
+/// A buffered counts file writer which compresses the output when set
+enum CountsWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl CountsWriter {
+    /// Creates the file at output_path with the compression given
+    fn create(output_path: &Path, compression_option: Option<Compression>) -> Result<Self> {
+        let file = BufWriter::new(File::create(output_path)?);
+        Ok(match compression_option {
+            Some(Compression::Gzip) => {
+                CountsWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Some(Compression::Zstd) => CountsWriter::Zstd(zstd::Encoder::new(file, 0)?),
+            None => CountsWriter::Plain(file),
+        })
+    }
+
+    /// Writes the end of the compressed stream, if compressed, and flushes the file.  Needs to be called once all rows are written
+    fn finish(self) -> Result<()> {
+        match self {
+            CountsWriter::Plain(mut file) => file.flush()?,
+            CountsWriter::Gzip(encoder) => encoder.finish()?.flush()?,
+            CountsWriter::Zstd(encoder) => encoder.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+impl Write for CountsWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CountsWriter::Plain(file) => file.write(buf),
+            CountsWriter::Gzip(encoder) => encoder.write(buf),
+            CountsWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CountsWriter::Plain(file) => file.flush(),
+            CountsWriter::Gzip(encoder) => encoder.flush(),
+            CountsWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Number of rows within each record batch of the Arrow counts files
+const ARROW_BATCH_ROWS: usize = 65_536;
+
+/// An Arrow IPC counts file writer.  Rows are added to the column builders and written as a record batch every ARROW_BATCH_ROWS rows
+/// so that the whole table is not held in memory
+struct ArrowCountsWriter {
+    writer: FileWriter<BufWriter<File>>,
+    schema: SchemaRef,
+    barcode_builders: Vec<StringBuilder>, // one for each counted barcode column
+    compound_id_builder: Option<StringBuilder>, // only when there are compound IDs
+    count_builders: Vec<UInt64Builder>, // one for each count column.  One for sample files and one per sample for merged files
+    rows: usize,                        // rows added since the last record batch
+}
+
+impl ArrowCountsWriter {
+    /// Creates the file at output_path with a string column for each barcode name, a Compound_ID column when with_compound_ids is
+    /// set, and an unsigned integer column for each count name.  Fails when any column names are the same
+    fn create(
+        output_path: &Path,
+        barcode_names: &[String],
+        with_compound_ids: bool,
+        count_names: &[String],
+        metadata: std::collections::HashMap<String, String>,
+    ) -> Result<Self> {
+        let mut fields = barcode_names
+            .iter()
+            .map(|barcode_name| Field::new(barcode_name, DataType::Utf8, false))
+            .collect::<Vec<Field>>();
+        if with_compound_ids {
+            // Combinations not within the expected combinations file have no compound ID
+            fields.push(Field::new("Compound_ID", DataType::Utf8, true));
+        }
+        fields.extend(
+            count_names
+                .iter()
+                .map(|count_name| Field::new(count_name, DataType::UInt64, false)),
+        );
+        // Columns are read by name, so a sample named the same as a barcode column or another sample would be lost
+        let field_names = fields
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<String>>();
+        if let Some(duplicate) = duplicate_column(&field_names) {
+            return Err(anyhow!(
+                "{} would have more than one '{}' column.  Sample IDs must be unique and differ from the barcode names",
+                output_path.display(),
+                duplicate
+            ));
+        }
+        let schema = Arc::new(Schema::new(fields).with_metadata(metadata));
+        let writer = FileWriter::try_new(BufWriter::new(File::create(output_path)?), &schema)?;
+        Ok(ArrowCountsWriter {
+            writer,
+            schema,
+            barcode_builders: barcode_names.iter().map(|_| StringBuilder::new()).collect(),
+            compound_id_builder: with_compound_ids.then(StringBuilder::new),
+            count_builders: count_names.iter().map(|_| UInt64Builder::new()).collect(),
+            rows: 0,
+        })
+    }
+
+    /// Adds a row from the counted barcode column values, the compound ID, and the counts
+    fn add_row(
+        &mut self,
+        barcode_values: &[&str],
+        compound_id: Option<&String>,
+        counts: &[u64],
+    ) -> Result<()> {
+        for (builder, barcode_value) in self.barcode_builders.iter_mut().zip(barcode_values) {
+            builder.append_value(barcode_value);
+        }
+        if let Some(builder) = self.compound_id_builder.as_mut() {
+            builder.append_option(compound_id);
+        }
+        for (builder, count) in self.count_builders.iter_mut().zip(counts) {
+            builder.append_value(*count);
+        }
+        self.rows += 1;
+        if self.rows == ARROW_BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the rows added since the last record batch as a new record batch
+    fn write_batch(&mut self) -> Result<()> {
+        let mut columns = self
+            .barcode_builders
+            .iter_mut()
+            .map(|builder| Arc::new(builder.finish()) as ArrayRef)
+            .collect::<Vec<ArrayRef>>();
+        if let Some(builder) = self.compound_id_builder.as_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        columns.extend(
+            self.count_builders
+                .iter_mut()
+                .map(|builder| Arc::new(builder.finish()) as ArrayRef),
+        );
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the end of the file.  Needs to be called once all rows are added
+    fn finish(mut self) -> Result<()> {
+        if self.rows > 0 {
+            self.write_batch()?;
+        }
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
 pub fn millisecond_decimal(elapsed_time: chrono::Duration) -> String {
     let milliseconds =
         (elapsed_time.num_milliseconds() - (elapsed_time.num_seconds() * 1000)).to_string();
@@ -587,17 +1629,37 @@ pub fn millisecond_decimal(elapsed_time: chrono::Duration) -> String {
     final_string
 }
 
-/// Converst the DNA sequence from counted barcodes to the ID
+/// Converst the DNA sequence from counted barcodes to the ID.  Barcodes without any conversions are left as the DNA sequence.  IDs
+/// containing a comma are quoted
 fn convert_code(code: &str, barcodes_hashmap: &[HashMap<String, String>]) -> String {
     code.split(',')
         .enumerate()
         .map(|(barcode_index, barcode)| {
             let barcode_hash = &barcodes_hashmap[barcode_index];
-            return barcode_hash.get(barcode).unwrap().to_string();
+            if barcode_hash.is_empty() {
+                barcode.to_string()
+            } else {
+                csv_field(barcode_hash.get(barcode).unwrap()).into_owned()
+            }
         })
         .join(",")
 }
 
+/// Quotes a value for a comma separated file when it contains a comma, quote, or line break.  Quotes within the value are doubled
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Returns the first column name that is already used by an earlier column, if any
+fn duplicate_column(columns: &[String]) -> Option<&String> {
+    let mut seen = AHashSet::new();
+    columns.iter().find(|column| !seen.insert(column.as_str()))
+}
+
 pub fn convert_sample_barcode(
     sample_barcode: &str,
     sample_barcodes_hash: &HashMap<String, String>,
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use regex::Captures;
 use std::{
+    borrow::Cow,
     collections::VecDeque,
     fmt,
     sync::{
@@ -9,45 +10,77 @@ use std::{
     },
 };
 
-use crate::info::{MaxSeqErrors, Results, SequenceErrors, SequenceFormat};
+use crate::info::{MaxSeqErrors, ReadOutcome, Results, SequenceErrors, SequenceFormat, Strand};
 use ahash::AHashSet;
 
+/// Holds everything needed to match reads to one sequence format along with where the errors for that format are recorded
+#[derive(Clone)]
+pub struct FormatMatcher {
+    pub sequence_format: SequenceFormat,
+    pub max_errors: MaxSeqErrors,
+    pub counted_barcode_seqs: Vec<AHashSet<String>>,
+    pub expected_combinations: Arc<AHashSet<String>>, // counted barcode combinations within the library.  Empty allows any combination
+    pub sequence_errors: SequenceErrors,
+    barcode_groups: Vec<String>,
+}
+
+impl FormatMatcher {
+    pub fn new(
+        sequence_format: SequenceFormat,
+        max_errors: MaxSeqErrors,
+        counted_barcode_seqs: Vec<AHashSet<String>>,
+        expected_combinations: Arc<AHashSet<String>>,
+        sequence_errors: SequenceErrors,
+    ) -> Self {
+        let mut barcode_groups = Vec::new();
+        for x in 0..sequence_format.barcode_num {
+            barcode_groups.push(format!("barcode{}", x + 1))
+        }
+        FormatMatcher {
+            sequence_format,
+            max_errors,
+            counted_barcode_seqs,
+            expected_combinations,
+            sequence_errors,
+            barcode_groups,
+        }
+    }
+}
+
 pub struct SequenceParser {
     shared_mut_clone: SharedMutData,
-    sequence_errors_clone: SequenceErrors,
-    sequence_format_clone: SequenceFormat,
-    max_errors_clone: MaxSeqErrors,
+    formats: Vec<FormatMatcher>,
     sample_seqs: AHashSet<String>,
-    counted_barcode_seqs: Vec<AHashSet<String>>,
     raw_sequence: RawSequenceRead,
-    barcode_groups: Vec<String>,
     min_quality_score: f32,
+    strand: Strand,
+    matched_strand: Strand,   // The strand the current read was matched on
+    format_index: usize,      // The index of the format the current read was assigned to
+    constant_ambiguous: bool, // Whether the constant region of the current read was tied between positions within a format
+    constant_mismatches: u16, // Mismatches within the constant region of the format the current read was assigned to
+    constant_mismatch_positions: Vec<usize>, // Format string positions of the constant region mismatches
 }
 
 impl SequenceParser {
     pub fn new(
         shared_mut_clone: SharedMutData,
-        sequence_errors_clone: SequenceErrors,
-        sequence_format_clone: SequenceFormat,
-        max_errors_clone: MaxSeqErrors,
+        formats: Vec<FormatMatcher>,
         sample_seqs: AHashSet<String>,
-        counted_barcode_seqs: Vec<AHashSet<String>>,
         min_quality_score: f32,
+        strand: Strand,
     ) -> Self {
-        let mut barcode_groups = Vec::new();
-        for x in 0..sequence_format_clone.barcode_num {
-            barcode_groups.push(format!("barcode{}", x + 1))
-        }
         SequenceParser {
             shared_mut_clone,
-            sequence_errors_clone,
-            sequence_format_clone,
-            max_errors_clone,
+            formats,
             sample_seqs,
-            counted_barcode_seqs,
             raw_sequence: RawSequenceRead::new(),
-            barcode_groups,
             min_quality_score,
+            strand,
+            matched_strand: Strand::Forward,
+            format_index: 0,
+            constant_ambiguous: false,
+            constant_mismatches: 0,
+            constant_mismatch_positions: Vec::new(),
         }
     }
     pub fn parse(&mut self) -> Result<()> {
@@ -57,15 +90,28 @@ impl SequenceParser {
                 if let Some(seq_match_result) = self.match_seq()? {
                     let barcode_string = seq_match_result.barcode_string();
                     // If there is a random barcode included
-                    let added = self.shared_mut_clone.results.lock().unwrap().add_count(
-                        &seq_match_result.sample_barcode,
-                        seq_match_result.random_barcode.as_ref(),
-                        barcode_string,
-                    );
+                    let added = self.shared_mut_clone.results[self.format_index]
+                        .lock()
+                        .unwrap()
+                        .add_count(
+                            &seq_match_result.sample_barcode,
+                            seq_match_result.random_barcode.as_ref(),
+                            barcode_string,
+                        );
+                    let sequence_errors = &mut self.formats[self.format_index].sequence_errors;
                     if added {
-                        self.sequence_errors_clone.correct_match()
+                        sequence_errors.correct_match();
+                        sequence_errors.strand_match(&self.matched_strand);
+                        sequence_errors.sample_read(
+                            Some(&seq_match_result.sample_barcode),
+                            ReadOutcome::Matched,
+                        );
                     } else {
-                        self.sequence_errors_clone.duplicated();
+                        sequence_errors.duplicated();
+                        sequence_errors.sample_read(
+                            Some(&seq_match_result.sample_barcode),
+                            ReadOutcome::Duplicate,
+                        );
                     }
                 }
             } else if self.shared_mut_clone.finished.load(Ordering::Relaxed) {
@@ -87,28 +133,57 @@ impl SequenceParser {
 
     /// Does a regex search and captures the barcodes.  Returns a struct of the results.  
     fn match_seq(&mut self) -> Result<Option<SequenceMatchResult>> {
-        self.check_and_fix_consant_region();
+        // If none of the formats match the read, record a constant region error and return None.  The constant region
+        // error counts are shared between all formats
+        if !self.find_strand() {
+            let sequence_errors = &mut self.formats[0].sequence_errors;
+            if self.constant_ambiguous {
+                sequence_errors.constant_region_ambiguous();
+            } else {
+                sequence_errors.constant_region_error();
+            }
+            sequence_errors.sample_read(None, ReadOutcome::ConstantRegionError);
+            return Ok(None);
+        }
+        let format = &mut self.formats[self.format_index];
         // if the barcodes are found continue, else return None and record a constant region error
-        if let Some(barcodes) = self
-            .sequence_format_clone
+        if let Some(barcodes) = format
+            .sequence_format
             .format_regex
             .captures(&self.raw_sequence.sequence)
         {
             // If there was a minimum set for quality, check each barcode's quality
-            if self.min_quality_score > 0.0 {
-                if let Some(format_match) = self
-                    .sequence_format_clone
+            if self.min_quality_score > 0.0
+                || !format.sequence_format.region_min_qualities.is_empty()
+            {
+                if let Some(format_match) = format
+                    .sequence_format
                     .format_regex
                     .find(&self.raw_sequence.sequence)
                 {
                     let start = format_match.start();
-                    if self.raw_sequence.low_quality(
-                        self.min_quality_score,
-                        &self.sequence_format_clone.regions_string,
-                        start,
-                    ) {
-                        // If any are low qualty, add to the low quality count and return
-                        self.sequence_errors_clone.low_quality_barcode();
+                    if (self.min_quality_score > 0.0
+                        && self.raw_sequence.low_quality(
+                            self.min_quality_score,
+                            &format.sequence_format.regions_string,
+                            start,
+                        ))
+                        || self.raw_sequence.low_quality_regions(
+                            &format.sequence_format.region_min_qualities,
+                            start,
+                        )
+                    {
+                        // If any are low qualty, add to the low quality count and return.  Only the sample barcode is looked up
+                        // so that the read is counted under its sample when it can be found
+                        format.sequence_errors.low_quality_barcode();
+                        let sample_barcode_option = find_sample_barcode(
+                            &barcodes,
+                            &self.sample_seqs,
+                            format.max_errors.max_sample_errors(),
+                        );
+                        format
+                            .sequence_errors
+                            .sample_read(sample_barcode_option.as_deref(), ReadOutcome::LowQuality);
                         return Ok(None);
                     }
                 } else {
@@ -121,44 +196,198 @@ impl SequenceParser {
             // Create a match results struct which tests the regex regions
             let match_results = SequenceMatchResult::new(
                 barcodes,
-                &self.barcode_groups,
-                &self.counted_barcode_seqs,
-                self.max_errors_clone.max_barcode_errors(),
+                &format.barcode_groups,
+                &format.counted_barcode_seqs,
+                format.max_errors.max_barcode_errors(),
                 &self.sample_seqs,
-                self.max_errors_clone.max_sample_errors(),
+                format.max_errors.max_sample_errors(),
             );
 
+            // Record how each region was matched
+            format
+                .sequence_errors
+                .constant_region_match(self.constant_mismatches);
+            if self.constant_mismatches > 0 {
+                format
+                    .sequence_errors
+                    .constant_region_corrected(&self.constant_mismatch_positions);
+            }
+            if let Some(mismatches) = match_results.sample_barcode_mismatches {
+                format.sequence_errors.sample_barcode_match(mismatches);
+            }
+            for (barcode_index, mismatches_option) in
+                match_results.counted_barcode_mismatches.iter().enumerate()
+            {
+                if let Some(mismatches) = mismatches_option {
+                    format
+                        .sequence_errors
+                        .barcode_match(barcode_index, *mismatches);
+                }
+            }
+
             // If the sample barcode was not found, record the error and return none so that the algorithm stops for this sequence
             if match_results.sample_barcode_error {
-                self.sequence_errors_clone.sample_barcode_error();
+                if match_results.sample_barcode_ambiguous {
+                    format.sequence_errors.sample_barcode_ambiguous();
+                } else {
+                    format.sequence_errors.sample_barcode_error();
+                }
+                format
+                    .sequence_errors
+                    .sample_read(None, ReadOutcome::SampleBarcodeError);
+                if let Some(failed_barcode) = &match_results.failed_barcode {
+                    format
+                        .sequence_errors
+                        .undetermined_sample_barcode(failed_barcode);
+                }
                 return Ok(None);
             }
             // If any of the counted barcodes were not found, even with error handling, record the error and return none so that the algorithm stops for this sequence
             if match_results.counted_barcode_error {
-                self.sequence_errors_clone.barcode_error();
+                // The counted barcode that failed is the one after the last barcode found
+                let barcode_index = match_results.counted_barcodes.len();
+                if match_results.counted_barcode_ambiguous {
+                    format.sequence_errors.barcode_ambiguous(barcode_index);
+                } else {
+                    format.sequence_errors.barcode_error(barcode_index);
+                }
+                format.sequence_errors.sample_read(
+                    Some(&match_results.sample_barcode),
+                    ReadOutcome::CountedBarcodeError,
+                );
+                if let Some(failed_barcode) = &match_results.failed_barcode {
+                    format
+                        .sequence_errors
+                        .unknown_barcode(barcode_index, failed_barcode);
+                }
+                return Ok(None);
+            }
+            // If an expected library was given, any other combination of counted barcodes cannot come from the library
+            if !format.expected_combinations.is_empty()
+                && !format
+                    .expected_combinations
+                    .contains(&match_results.barcode_string())
+            {
+                format.sequence_errors.impossible_combination();
+                format.sequence_errors.sample_read(
+                    Some(&match_results.sample_barcode),
+                    ReadOutcome::ImpossibleCombination,
+                );
                 return Ok(None);
             }
             // If all went well, return the match results struct
             Ok(Some(match_results))
         } else {
             // If the constant region was not found, record the error and return None
-            self.sequence_errors_clone.constant_region_error();
+            format.sequence_errors.constant_region_error();
+            format
+                .sequence_errors
+                .sample_read(None, ReadOutcome::ConstantRegionError);
             Ok(None)
         }
     }
 
-    /// Checks the constant region of the sequence then finds the best fix if it is not found.  Basically whether or not the regex search worked
-    fn check_and_fix_consant_region(&mut self) {
-        // If the regex search does not work, try to fix the constant region
-        if !self
-            .sequence_format_clone
-            .format_regex
-            .is_match(&self.raw_sequence.sequence)
-        {
-            self.raw_sequence.fix_constant_region(
-                &self.sequence_format_clone.format_string,
-                self.max_errors_clone.max_constant_errors(),
-            );
+    /// Orients the read according to the strand setting and assigns it to a format, fixing the constant region on the way.  With 'Both', the reverse
+    /// complement is only tried when the forward read cannot be matched to any format
+    fn find_strand(&mut self) -> bool {
+        self.reset_match_state();
+        match self.strand {
+            Strand::Forward => {
+                self.matched_strand = Strand::Forward;
+                self.assign_format()
+            }
+            Strand::Reverse => {
+                self.matched_strand = Strand::Reverse;
+                self.raw_sequence.reverse_complement();
+                self.assign_format()
+            }
+            Strand::Both => {
+                // The reverse strand is tried on its own copy of the read with fresh match state, since a failed forward attempt
+                // can already have fixed part of the constant region
+                let mut reverse_sequence = self.raw_sequence.clone();
+                reverse_sequence.reverse_complement();
+                self.matched_strand = Strand::Forward;
+                if self.assign_format() {
+                    return true;
+                }
+                let forward_ambiguous = self.constant_ambiguous;
+                self.raw_sequence = reverse_sequence;
+                self.reset_match_state();
+                self.matched_strand = Strand::Reverse;
+                if self.assign_format() {
+                    return true;
+                }
+                // Neither strand matched, so the read is ambiguous when either strand had a tied best match
+                self.constant_ambiguous |= forward_ambiguous;
+                false
+            }
+        }
+    }
+
+    /// Clears the constant region match state left by the previous read or strand
+    fn reset_match_state(&mut self) {
+        self.constant_ambiguous = false;
+        self.constant_mismatches = 0;
+        self.constant_mismatch_positions.clear();
+    }
+
+    /// Assigns the read to the format it matches best and fixes the constant region for that format.  The best format has the fewest
+    /// constant region mismatches, with ties going to the longer format.  Returns false if no format matches within the allowed errors.
+    /// When no format matches and any format had a tied best match, the read is marked as constant region ambiguous
+    fn assign_format(&mut self) -> bool {
+        // (format index, fixed sequence, mismatches).  A fixed sequence of None means the regex search worked without a fix
+        let mut best_format: Option<(usize, Option<String>, u16)> = None;
+        for (index, format) in self.formats.iter().enumerate() {
+            let format_match = if format
+                .sequence_format
+                .format_regex
+                .is_match(&self.raw_sequence.sequence)
+            {
+                Some((None, 0))
+            } else {
+                match self.raw_sequence.constant_region_match(
+                    &format.sequence_format.format_string,
+                    format.max_errors.max_constant_errors(),
+                ) {
+                    BarcodeMatch::Found(fixed_sequence, mismatches) => {
+                        Some((Some(fixed_sequence), mismatches))
+                    }
+                    BarcodeMatch::Ambiguous => {
+                        self.constant_ambiguous = true;
+                        None
+                    }
+                    BarcodeMatch::NoMatch => None,
+                }
+            };
+            if let Some((fixed_sequence_option, mismatches)) = format_match {
+                let better = match &best_format {
+                    Some((best_index, _, best_mismatches)) => {
+                        mismatches < *best_mismatches
+                            || (mismatches == *best_mismatches
+                                && format.sequence_format.length
+                                    > self.formats[*best_index].sequence_format.length)
+                    }
+                    None => true,
+                };
+                if better {
+                    best_format = Some((index, fixed_sequence_option, mismatches));
+                }
+            }
+        }
+
+        if let Some((index, fixed_sequence_option, mismatches)) = best_format {
+            self.format_index = index;
+            self.constant_mismatches = mismatches;
+            let format = &self.formats[index].sequence_format;
+            if let Some(fixed_sequence) = fixed_sequence_option {
+                self.constant_mismatch_positions =
+                    mismatch_positions(&fixed_sequence, &format.format_string);
+                self.raw_sequence
+                    .insert_barcodes_constant_region(&format.format_string, fixed_sequence);
+            }
+            format.format_regex.is_match(&self.raw_sequence.sequence)
+        } else {
+            false
         }
     }
 }
@@ -166,14 +395,14 @@ impl SequenceParser {
 pub struct SharedMutData {
     pub seq: Arc<Mutex<VecDeque<String>>>,
     pub finished: Arc<AtomicBool>,
-    pub results: Arc<Mutex<Results>>,
+    pub results: Vec<Arc<Mutex<Results>>>, // One results struct for each sequence format
 }
 
 impl SharedMutData {
     pub fn new(
         seq: Arc<Mutex<VecDeque<String>>>,
         finished: Arc<AtomicBool>,
-        results: Arc<Mutex<Results>>,
+        results: Vec<Arc<Mutex<Results>>>,
     ) -> Self {
         SharedMutData {
             seq,
@@ -185,7 +414,7 @@ impl SharedMutData {
     pub fn arc_clone(&self) -> SharedMutData {
         let seq = Arc::clone(&self.seq);
         let finished = Arc::clone(&self.finished);
-        let results = Arc::clone(&self.results);
+        let results = self.results.iter().map(Arc::clone).collect();
         SharedMutData {
             seq,
             finished,
@@ -274,9 +503,9 @@ impl RawSequenceRead {
         // sequence corresponding to the barcodes
         for (old_char, new_char) in best_sequence.chars().zip(format_string.chars()) {
             if new_char == 'N' {
-                fixed_sequence.push_str(&old_char.to_string());
+                fixed_sequence.push(old_char);
             } else {
-                fixed_sequence.push_str(&new_char.to_string());
+                fixed_sequence.push(new_char);
             }
         }
         self.sequence = fixed_sequence
@@ -285,10 +514,26 @@ impl RawSequenceRead {
     /// Fixes the constant region by finding the closest match within the full seqeuence that has fewer than the max errors allowed,
     /// then uses the format string to flip the barcodes into the 'N's and have a fixed constant region string
     pub fn fix_constant_region(&mut self, format_string: &str, max_constant_errors: u16) {
+        if let BarcodeMatch::Found(best_sequence, _) =
+            self.constant_region_match(format_string, max_constant_errors)
+        {
+            self.insert_barcodes_constant_region(format_string, best_sequence);
+        } else {
+            self.sequence = "".to_string();
+        }
+    }
+
+    /// Finds the region of the sequence that best matches the format string with fewer than the max errors allowed.  Returns
+    /// the matching region along with the number of mismatches, or whether there was no match or more than one best match
+    pub fn constant_region_match(
+        &self,
+        format_string: &str,
+        max_constant_errors: u16,
+    ) -> BarcodeMatch {
         // Find the region of the sequence that best matches the constant region.  This is doen by iterating through the sequence
         // Get the length difference between what was sequenced and the barcode region with constant regions
         // This is to stop the iteration in the next step
-        let length_diff = self.sequence.len() - format_string.len();
+        let length_diff = self.sequence.len().saturating_sub(format_string.len());
 
         // Create a vector of sequences the length of the constant region + barcodes to check for where the best match is located
         let mut possible_seqs = Vec::new();
@@ -303,13 +548,40 @@ impl RawSequenceRead {
             possible_seqs.push(possible_seq);
         }
         // Find the closest match within what was sequenced to the constant region
-        let best_sequence_option = fix_error(format_string, &possible_seqs, max_constant_errors);
+        best_match(format_string, &possible_seqs, max_constant_errors)
+    }
 
-        if let Some(best_sequence) = best_sequence_option {
-            self.insert_barcodes_constant_region(format_string, best_sequence);
-        } else {
-            self.sequence = "".to_string();
-        }
+    /// Reverse complements the sequence and reverses the quality values so that a read from the opposite strand
+    /// can be searched with the same sequence format
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::parse::RawSequenceRead;
+    ///
+    /// let mut raw_sequence_read = RawSequenceRead::new_fill(
+    ///     "@read".to_string(),
+    ///     "AAGCN".to_string(),
+    ///     "+".to_string(),
+    ///     "ABCDE".to_string(),
+    /// );
+    /// raw_sequence_read.reverse_complement();
+    /// assert_eq!(raw_sequence_read.sequence, "NGCTT");
+    /// assert_eq!(raw_sequence_read.quality_scores(), vec![36, 35, 34, 33, 32]);
+    /// ```
+    pub fn reverse_complement(&mut self) {
+        self.sequence = self
+            .sequence
+            .chars()
+            .rev()
+            .map(|nucleotide| match nucleotide {
+                'A' => 'T',
+                'T' => 'A',
+                'G' => 'C',
+                'C' => 'G',
+                other => other,
+            })
+            .collect();
+        self.quality_values = self.quality_values.chars().rev().collect();
     }
 
     /// Each DNA base read score within FASTQ is the ascii number - 33.
@@ -374,6 +646,46 @@ impl RawSequenceRead {
         false
     }
 
+    /// Test for if any region with its own quality threshold has an average quality score below that threshold.  Each
+    /// region is given as (start, end, minimum average) relative to the start of the format within the read
+    ///
+    /// # Example
+    /// ```
+    /// use barcode_count::parse::RawSequenceRead;
+    ///
+    /// let raw_sequence_read = RawSequenceRead::new_fill(
+    ///     "@read".to_string(),
+    ///     "AAGCTT".to_string(),
+    ///     "+".to_string(),
+    ///     "II((II".to_string(),
+    /// );
+    /// assert!(raw_sequence_read.low_quality_regions(&[(2, 4, 20.0)], 0));
+    /// assert!(!raw_sequence_read.low_quality_regions(&[(0, 2, 20.0), (4, 6, 20.0)], 0));
+    /// ```
+    pub fn low_quality_regions(
+        &self,
+        region_min_qualities: &[(usize, usize, f32)],
+        start: usize,
+    ) -> bool {
+        let quality_scores = self.quality_scores();
+        for (region_start, region_end, min_average) in region_min_qualities {
+            let scores = quality_scores
+                .iter()
+                .skip(start + region_start)
+                .take(region_end - region_start)
+                .map(|score| *score as f32)
+                .collect::<Vec<f32>>();
+            if scores.is_empty() {
+                continue;
+            }
+            let average_score = scores.iter().sum::<f32>() / scores.len() as f32;
+            if average_score < *min_average {
+                return true;
+            }
+        }
+        false
+    }
+
     pub fn check_fastq_format(&self) -> Result<()> {
         // Test to see if the first line is not a sequence and the second is a sequence, which is typical fastq format
         match test_sequence(&self.description) {
@@ -432,6 +744,11 @@ pub struct SequenceMatchResult {
     pub counted_barcodes: Vec<String>,
     pub counted_barcode_error: bool,
     pub sample_barcode_error: bool,
+    pub counted_barcode_ambiguous: bool, // Whether the counted barcode error was from a tie between known barcodes
+    pub sample_barcode_ambiguous: bool, // Whether the sample barcode error was from a tie between known barcodes
+    pub counted_barcode_mismatches: Vec<Option<u16>>, // Mismatches fixed within each counted barcode found.  None if it was not checked against known barcodes
+    pub sample_barcode_mismatches: Option<u16>, // Mismatches fixed within the sample barcode.  None if it was not checked against known barcodes
+    pub failed_barcode: Option<String>, // Raw sequence of the sample or counted barcode that could not be fixed
     pub random_barcode: Option<String>,
 }
 
@@ -446,6 +763,9 @@ impl SequenceMatchResult {
     ) -> SequenceMatchResult {
         // Check for sample barcode and start with setting error to false
         let mut sample_barcode_error = false;
+        let mut sample_barcode_ambiguous = false;
+        let mut sample_barcode_mismatches = None;
+        let mut failed_barcode = None;
         let sample_barcode;
         // If 'sample' is within the regex returned search continue with checking and fixing
         if let Some(sample_barcode_match) = barcodes.name("sample") {
@@ -456,15 +776,20 @@ impl SequenceMatchResult {
                 // If the sample barcode is known save it
                 if sample_seqs.contains(sample_barcode_str) {
                     sample_barcode = sample_barcode_str.to_string();
+                    sample_barcode_mismatches = Some(0);
                 } else {
-                    // Otherwise try and fix it.  If the fix returns none, then save the error and an empty string
-                    let sample_barcode_fix_option =
-                        fix_error(sample_barcode_str, sample_seqs, sample_seqs_max_errors);
-                    if let Some(fixed_barcode) = sample_barcode_fix_option {
-                        sample_barcode = fixed_barcode;
-                    } else {
-                        sample_barcode = String::new();
-                        sample_barcode_error = true;
+                    // Otherwise try and fix it.  If the fix does not find a single match, then save the error and an empty string
+                    match best_match(sample_barcode_str, sample_seqs, sample_seqs_max_errors) {
+                        BarcodeMatch::Found(fixed_barcode, mismatches) => {
+                            sample_barcode = fixed_barcode;
+                            sample_barcode_mismatches = Some(mismatches);
+                        }
+                        barcode_match => {
+                            sample_barcode = String::new();
+                            sample_barcode_error = true;
+                            failed_barcode = Some(sample_barcode_str.to_string());
+                            sample_barcode_ambiguous = barcode_match == BarcodeMatch::Ambiguous;
+                        }
                     }
                 }
             }
@@ -475,34 +800,45 @@ impl SequenceMatchResult {
 
         // Check the counted barcodes and start with setting the error to false
         let mut counted_barcode_error = false;
+        let mut counted_barcode_ambiguous = false;
         // Create an empty vec to hold the barcodes
         let mut counted_barcodes = Vec::new();
+        let mut counted_barcode_mismatches = Vec::new();
         // Only continue if the sample barcode was found
         if !sample_barcode_error {
             // Iterate through the counted barcocdes.  Fix if they are not within the known barcodes
             for (index, barcode_group) in barcode_groups.iter().enumerate() {
                 let mut counted_barcode =
                     barcodes.name(barcode_group).unwrap().as_str().to_string();
+                let mut mismatches_option = None;
                 // If a barcode conversion file was included and there are known barcodes, check for sequencing errors
-                if !counted_barcode_seqs.is_empty() {
+                if !counted_barcode_seqs.is_empty() && !counted_barcode_seqs[index].is_empty() {
+                    mismatches_option = Some(0);
                     // If the barcode is not known, try and fix
                     if !counted_barcode_seqs[index].contains(&counted_barcode) {
-                        let barcode_seq_fix_option = fix_error(
+                        match best_match(
                             &counted_barcode,
                             &counted_barcode_seqs[index],
                             counted_barcode_max_errors[index],
-                        );
-                        if let Some(fixed_barcode) = barcode_seq_fix_option {
-                            counted_barcode = fixed_barcode;
-                        } else {
-                            // If a fix was not found, return the error and stop going through more barcodes
-                            counted_barcode_error = true;
-                            break;
+                        ) {
+                            BarcodeMatch::Found(fixed_barcode, mismatches) => {
+                                counted_barcode = fixed_barcode;
+                                mismatches_option = Some(mismatches);
+                            }
+                            barcode_match => {
+                                // If a fix was not found, return the error and stop going through more barcodes
+                                counted_barcode_error = true;
+                                counted_barcode_ambiguous =
+                                    barcode_match == BarcodeMatch::Ambiguous;
+                                failed_barcode = Some(counted_barcode);
+                                break;
+                            }
                         }
                     }
                 }
                 // If all is well, add the counted barcode to the vec
                 counted_barcodes.push(counted_barcode);
+                counted_barcode_mismatches.push(mismatches_option);
             }
         }
 
@@ -519,6 +855,11 @@ impl SequenceMatchResult {
             counted_barcodes,
             counted_barcode_error,
             sample_barcode_error,
+            counted_barcode_ambiguous,
+            sample_barcode_ambiguous,
+            counted_barcode_mismatches,
+            sample_barcode_mismatches,
+            failed_barcode,
             random_barcode,
         }
     }
@@ -529,6 +870,26 @@ impl SequenceMatchResult {
     }
 }
 
+/// Returns the sample barcode within the regex captures, fixed against the known sample barcodes when needed.  Returns None if the sample barcode
+/// could not be found.  Used for reads that are not counted, so the counted barcodes are not checked
+fn find_sample_barcode<'a>(
+    barcodes: &Captures<'a>,
+    sample_seqs: &AHashSet<String>,
+    sample_seqs_max_errors: u16,
+) -> Option<Cow<'a, str>> {
+    let sample_barcode_str = match barcodes.name("sample") {
+        Some(sample_barcode_match) => sample_barcode_match.as_str(),
+        None => return Some(Cow::Borrowed("barcode")),
+    };
+    if sample_seqs.is_empty() || sample_seqs.contains(sample_barcode_str) {
+        return Some(Cow::Borrowed(sample_barcode_str));
+    }
+    match best_match(sample_barcode_str, sample_seqs, sample_seqs_max_errors) {
+        BarcodeMatch::Found(fixed_barcode, _) => Some(Cow::Owned(fixed_barcode)),
+        _ => None,
+    }
+}
+
 /// Fix an error in a sequence by comparing it to all possible sequences.  If no sequence matches with fewer or equal to the number of mismatches 'None' is returned.
 /// 'None' is also returned if two or more sequences are best matches.  Will work with vec and hashset
 ///
@@ -539,8 +900,8 @@ impl SequenceMatchResult {
 ///
 /// let barcode = "AGTAG";
 ///
-/// let possible_barcodes_one_match: std::collections::AHashSet<String> = ["AGCAG".to_string(), "ACAAG".to_string(), "AGCAA".to_string()].iter().cloned().collect(); // only the first has a single mismatch
-/// let possible_barcodes_two_match: std::collections::AHashSet<String> = ["AGCAG".to_string(), "AGAAG".to_string(), "AGCAA".to_string()].iter().cloned().collect(); // first and second have a single mismatch
+/// let possible_barcodes_one_match: ahash::AHashSet<String> = ["AGCAG".to_string(), "ACAAG".to_string(), "AGCAA".to_string()].iter().cloned().collect(); // only the first has a single mismatch
+/// let possible_barcodes_two_match: ahash::AHashSet<String> = ["AGCAG".to_string(), "AGAAG".to_string(), "AGCAA".to_string()].iter().cloned().collect(); // first and second have a single mismatch
 ///
 /// let max_mismatches = barcode.chars().count() as u16 / 5; // allow up to 20% mismatches
 ///
@@ -551,6 +912,60 @@ impl SequenceMatchResult {
 /// assert_eq!(fixed_error_two, None);
 /// ```
 pub fn fix_error<'a, I>(mismatch_seq: &str, possible_seqs: I, mismatches: u16) -> Option<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    match best_match(mismatch_seq, possible_seqs, mismatches) {
+        BarcodeMatch::Found(best_seq, _) => Some(best_seq),
+        _ => None,
+    }
+}
+
+/// Returns the positions where the sequence does not match the format string.  'N's within either are not mismatches
+///
+/// # Example
+///
+/// ```
+/// use barcode_count::parse::mismatch_positions;
+///
+/// assert_eq!(mismatch_positions("AGTTGCAG", "AGCTNNAC"), vec![2, 7]);
+/// ```
+pub fn mismatch_positions(sequence: &str, format_string: &str) -> Vec<usize> {
+    sequence
+        .chars()
+        .zip(format_string.chars())
+        .enumerate()
+        .filter(|(_, (sequence_char, format_char))| {
+            sequence_char != format_char && *sequence_char != 'N' && *format_char != 'N'
+        })
+        .map(|(position, _)| position)
+        .collect()
+}
+
+/// The result of comparing a sequence against all possible sequences
+#[derive(Debug, Clone, PartialEq)]
+pub enum BarcodeMatch {
+    Found(String, u16), // The single best match and its number of mismatches
+    Ambiguous, // Two or more sequences are tied for the best match within the allowed mismatches
+    NoMatch,   // No sequence is within the allowed mismatches
+}
+
+/// Same as fix_error, but also returns the number of mismatches between the sequence and the best match, and separates
+/// a tie between best matches from no match
+///
+/// # Example
+///
+/// ```
+/// use barcode_count::parse::{best_match, BarcodeMatch};
+///
+/// let possible_barcodes: ahash::AHashSet<String> = ["AGCAG".to_string(), "ACAAG".to_string()].iter().cloned().collect();
+///
+/// assert_eq!(best_match("AGTAG", &possible_barcodes, 1), BarcodeMatch::Found("AGCAG".to_string(), 1));
+/// assert_eq!(best_match("AGCAG", &possible_barcodes, 1), BarcodeMatch::Found("AGCAG".to_string(), 0));
+/// assert_eq!(best_match("TTTTT", &possible_barcodes, 1), BarcodeMatch::NoMatch);
+/// assert_eq!(best_match("ACCAG", &possible_barcodes, 1), BarcodeMatch::Ambiguous);
+/// ```
+pub fn best_match<'a, I>(mismatch_seq: &str, possible_seqs: I, mismatches: u16) -> BarcodeMatch
 where
     I: IntoIterator<Item = &'a String>,
 {
@@ -584,10 +999,10 @@ where
             best_match = Some(true_seq.to_string());
         }
     }
-    // If there is one best match and it is some, return it.  Otherwise return None
-    if keep && best_match.is_some() {
-        best_match
-    } else {
-        None
+    // If there is one best match return it.  A tie is only ambiguous when the tied sequences are within the allowed mismatches
+    match best_match {
+        Some(best_seq) if keep => BarcodeMatch::Found(best_seq, best_mismatch_count),
+        Some(_) => BarcodeMatch::Ambiguous,
+        None => BarcodeMatch::NoMatch,
     }
 }
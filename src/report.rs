@@ -0,0 +1,139 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::info::{ErrorCounts, SampleReadCounts};
+
+/// Version of the JSON report layout.  Increased whenever a field is renamed or removed so that readers can check
+/// compatibility.  Added fields do not change the version
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// The machine readable report written for each sequence format at the end of a run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    pub schema_version: u32,
+    pub tool: ToolReport,
+    pub timing: TimingReport,
+    pub inputs: InputReport,
+    pub settings: SettingsReport,
+    pub format: FormatReport,
+    pub max_errors: MaxErrorsReport,
+    pub reads: ReadsReport,
+    pub region_matches: Vec<RegionReport>,
+    pub samples: Vec<SampleReport>,
+    pub constant_mismatch_profile: MismatchProfileReport,
+    pub output_files: Vec<OutputFileReport>,
+}
+
+/// Name and version of the program that created the report
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolReport {
+    pub name: String,
+    pub version: String,
+}
+
+/// Start and finish times in RFC 3339 format with the total run time
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingReport {
+    pub start: String,
+    pub finish: String,
+    pub total_seconds: f64,
+}
+
+/// Input file paths.  Conversion files are null when not used
+#[derive(Debug, Clone, Serialize)]
+pub struct InputReport {
+    pub fastq: String,
+    pub format: String,
+    pub sample_barcodes: Option<String>,
+    pub counted_barcodes: Option<String>,
+    pub expected_combinations: Option<String>,
+}
+
+/// Command line settings that change how reads are counted or written
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsReport {
+    pub strand: String,
+    pub threads: u16,
+    pub merge_output: bool,
+    pub enrich: bool,
+    pub sort: String,
+    pub compress: Option<String>,
+    pub arrow: bool,
+    pub long: bool,
+    pub mtx: bool,
+    pub sequence_and_id: bool,
+    pub include_zero: bool,
+    pub top_barcodes: usize,
+    pub fail_on_collision: bool,
+    pub multiqc: bool,
+}
+
+/// The sequence format the counts are for
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatReport {
+    pub format_string: String,  // sequence with 'N's replacing barcodes
+    pub regions_string: String, // region code for each position of the format string
+    pub barcode_names: Vec<String>,
+    pub barcode_lengths: Vec<u16>,
+    pub sample_barcode_length: Option<u16>,
+    pub random_barcode: bool,
+}
+
+/// Maximum mismatches allowed within each region and the minimum average quality score
+#[derive(Debug, Clone, Serialize)]
+pub struct MaxErrorsReport {
+    pub constant_region: u16,
+    pub sample_barcode: u16,
+    pub counted_barcodes: Vec<u16>,
+    pub min_quality: f32,
+}
+
+/// Total reads along with every error category count
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadsReport {
+    pub total: u32,
+    #[serde(flatten)]
+    pub counts: ErrorCounts,
+}
+
+/// Matches of a single region, where index 0 of mismatches is exact matches and each following index is the reads
+/// corrected with that many mismatches
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionReport {
+    pub region: String,
+    pub mismatches: Vec<u32>,
+    pub failed: u32,
+}
+
+/// Read outcomes of a single sample.  The last sample is 'undetermined', holding the reads that failed before the sample
+/// barcode was known
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleReport {
+    pub sample: String,
+    pub metadata: BTreeMap<String, String>, // extra sample file columns of the sample.  Empty without them
+    #[serde(flatten)]
+    pub reads: SampleReadCounts,
+}
+
+/// Mismatches at each constant nucleotide over the reads that needed their constant region corrected
+#[derive(Debug, Clone, Serialize)]
+pub struct MismatchProfileReport {
+    pub file: String,
+    pub corrected: u32,
+    pub positions: Vec<PositionReport>,
+}
+
+/// Mismatches at a single position of the format string.  Positions start at 1
+#[derive(Debug, Clone, Serialize)]
+pub struct PositionReport {
+    pub position: usize,
+    pub nucleotide: char,
+    pub mismatches: u32,
+}
+
+/// A counts file that was written along with the number of barcodes within it
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputFileReport {
+    pub file: String,
+    pub barcodes_counted: usize,
+}